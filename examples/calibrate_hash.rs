@@ -0,0 +1,40 @@
+//! Picks Argon2 costs for this machine and prints them as `HashServiceConfig` JSON.
+//!
+//! Only the Argon2 fields are printed: the concurrency cap depends on the host and the
+//! legacy salt and peppers are secrets, so those stay in each deployment's own config.
+//!
+//! ```sh
+//! cargo run --release --features auth --example calibrate_hash -- [target_ms] [max_memory_kib]
+//! ```
+//!
+//! Defaults to a 500 ms target and 64 MiB of memory.
+
+use lunna_actix_utils::auth::service::hash_service::HashService;
+use std::time::Duration;
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let target_ms: u64 = args
+        .next()
+        .map(|arg| arg.parse().expect("target_ms must be a number"))
+        .unwrap_or(500);
+    let max_memory: u32 = args
+        .next()
+        .map(|arg| arg.parse().expect("max_memory_kib must be a number"))
+        .unwrap_or(64 * 1024);
+
+    let config = HashService::calibrate(Duration::from_millis(target_ms), max_memory)
+        .expect("calibration failed");
+
+    let params = serde_json::json!({
+        "algorithm": config.algorithm,
+        "memory_cost": config.memory_cost,
+        "time_cost": config.time_cost,
+        "parallelism": config.parallelism,
+    });
+
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&params).expect("params are serializable")
+    );
+}
@@ -1,3 +1,6 @@
+use actix_web::http::StatusCode;
+use actix_web::http::header::WWW_AUTHENTICATE;
+use actix_web::{HttpResponse, ResponseError};
 use serde::{Serialize, Serializer};
 use serde::ser::SerializeMap;
 use strum::{AsRefStr, EnumString};
@@ -12,12 +15,16 @@ pub enum AuthError {
   InvalidEmail,
   #[error("Invalid password, check the password requirements")]
   InvalidPassword,
+  #[error("The password has appeared in a data breach, choose a different one")]
+  BreachedPassword,
   #[error("The email is already taken")]
   EmailAlreadyInUse,
   #[error("The email already taken")]
   UsernameAlreadyInUse,
   #[error("Invalid captcha")]
   InvalidCaptcha,
+  #[error("No authentication token was provided")]
+  MissingToken,
   #[error("Invalid token")]
   InvalidToken,
   #[error("Token expired")]
@@ -26,8 +33,36 @@ pub enum AuthError {
   TokenNotFound,
   #[error("Token not valid")]
   TokenNotValid,
+  #[error("You don't have permission to access this resource")]
+  Forbidden,
+  #[error("Missing or invalid CSRF token")]
+  InvalidCsrfToken,
+  #[error("Invalid client credentials")]
+  InvalidClient,
+  #[error("Unsupported grant type")]
+  UnsupportedGrantType,
+  #[error("Unsupported token type")]
+  UnsupportedTokenType,
+  #[error("The subject token is invalid, expired or revoked")]
+  InvalidGrant,
+  #[error("The requested scope exceeds the scope of the subject token")]
+  InvalidScope,
+  #[error("The requested audience is not allowed for this client")]
+  InvalidTarget,
+  #[error("Token revoked")]
+  TokenRevoked,
+  #[error("A revocation store is configured, tokens must be verified asynchronously")]
+  RevocationCheckRequired,
+  #[error("The token was already used, every session started from it has been revoked")]
+  TokenReused,
+  #[error("Too many authentication requests in progress, try again later")]
+  TooManyRequests,
   #[error("No private key was provided")]
   NoPrivateKey,
+  #[error("The configured key is invalid")]
+  InvalidKey,
+  #[error("Key not found")]
+  KeyNotFound,
   #[error("Internal error during authentication")]
   InternalError,
 }
@@ -51,4 +86,71 @@ impl Serialize for AuthError {
     map.serialize_entry("key", &i18n_key)?;
     map.end()
   }
-}
\ No newline at end of file
+}
+
+/// Lets handlers and extractors return an `AuthError` directly.
+///
+/// The body is the same `{ "error", "key" }` object as the serialized error. Token errors
+/// are `401 Unauthorized` with a `WWW-Authenticate: Bearer` challenge (RFC 6750).
+impl ResponseError for AuthError {
+  fn status_code(&self) -> StatusCode {
+    match self {
+      AuthError::InvalidUsernameOrPassword
+      | AuthError::MissingToken
+      | AuthError::InvalidToken
+      | AuthError::TokenExpired
+      | AuthError::TokenNotFound
+      | AuthError::TokenNotValid
+      | AuthError::TokenRevoked
+      | AuthError::TokenReused
+      | AuthError::InvalidClient => StatusCode::UNAUTHORIZED,
+      AuthError::Forbidden | AuthError::InvalidCsrfToken => StatusCode::FORBIDDEN,
+      AuthError::EmailAlreadyInUse | AuthError::UsernameAlreadyInUse => StatusCode::CONFLICT,
+      AuthError::TooManyRequests => StatusCode::TOO_MANY_REQUESTS,
+      AuthError::NoPrivateKey
+      | AuthError::InvalidKey
+      | AuthError::KeyNotFound
+      | AuthError::RevocationCheckRequired
+      | AuthError::InternalError => StatusCode::INTERNAL_SERVER_ERROR,
+      AuthError::InvalidEmail
+      | AuthError::InvalidPassword
+      | AuthError::BreachedPassword
+      | AuthError::InvalidCaptcha
+      | AuthError::UnsupportedGrantType
+      | AuthError::UnsupportedTokenType
+      | AuthError::InvalidGrant
+      | AuthError::InvalidScope
+      | AuthError::InvalidTarget => StatusCode::BAD_REQUEST,
+    }
+  }
+
+  fn error_response(&self) -> HttpResponse {
+    let mut response = HttpResponse::build(self.status_code());
+
+    match self {
+      AuthError::MissingToken => {
+        response.insert_header((WWW_AUTHENTICATE, "Bearer"));
+      }
+      AuthError::InvalidToken
+      | AuthError::TokenExpired
+      | AuthError::TokenNotFound
+      | AuthError::TokenNotValid
+      | AuthError::TokenRevoked
+      | AuthError::TokenReused => {
+        response.insert_header((
+          WWW_AUTHENTICATE,
+          format!(r#"Bearer error="invalid_token", error_description="{}""#, self),
+        ));
+      }
+      AuthError::Forbidden => {
+        response.insert_header((WWW_AUTHENTICATE, r#"Bearer error="insufficient_scope""#));
+      }
+      AuthError::InvalidClient => {
+        response.insert_header((WWW_AUTHENTICATE, "Basic"));
+      }
+      _ => {}
+    }
+
+    response.json(self)
+  }
+}
@@ -0,0 +1,291 @@
+use crate::auth::error::AuthError;
+use crate::auth::service::token_claims::JwtDataContainer;
+use crate::auth::service::token_codec::{app_token_codec, from_value};
+use actix_web::http::header::AUTHORIZATION;
+use actix_web::{FromRequest, HttpMessage, HttpRequest, dev::Payload};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::future::Future;
+use std::ops;
+use std::pin::Pin;
+
+/// The verified claims of the `Bearer` token sent with a request.
+///
+/// The token is read from the `Authorization` header and verified with the token codec
+/// registered in the app, revocation included: a `web::Data<dyn TokenCodec>`, or else,
+/// with the `jwt` feature, a `web::Data<JwtService>`. See
+/// [`TokenCodec`](crate::auth::service::token_codec::TokenCodec). A missing or rejected
+/// token ends the request with the matching [`AuthError`], so token errors become
+/// `401 Unauthorized` with a `WWW-Authenticate` challenge.
+///
+/// Behind [`RequireAuthorization`](crate::auth::middleware::authorization::RequireAuthorization),
+/// the claims it already verified are reused instead of verifying the token again.
+///
+/// For endpoints where authentication is optional, use
+/// [`MaybeAuthenticated`](super::maybe_authenticated::MaybeAuthenticated) rather than
+/// `Option<Authenticated<T>>`: the latter also gets `None` for an invalid or revoked
+/// token, and for a server error.
+///
+/// # Example
+/// ```
+/// use actix_web::get;
+/// use lunna_actix_utils::auth::extractors::authenticated::Authenticated;
+/// use lunna_actix_utils::auth::extractors::maybe_authenticated::MaybeAuthenticated;
+///
+/// #[get("/me")]
+/// async fn me(user: Authenticated<String>) -> String {
+///     format!("Hello, {}!", user.data)
+/// }
+///
+/// #[get("/greeting")]
+/// async fn greeting(user: MaybeAuthenticated<String>) -> String {
+///     match user.into_inner() {
+///         Some(user) => format!("Welcome back, {}!", user.data),
+///         None => "Welcome!".to_string(),
+///     }
+/// }
+/// ```
+pub struct Authenticated<T>(pub JwtDataContainer<T>)
+where
+    T: Serialize + DeserializeOwned;
+
+impl<T> Authenticated<T>
+where
+    T: Serialize + DeserializeOwned,
+{
+    /// Consumes the wrapper and returns the claims.
+    pub fn into_inner(self) -> JwtDataContainer<T> {
+        self.0
+    }
+}
+
+impl<T> ops::Deref for Authenticated<T>
+where
+    T: Serialize + DeserializeOwned,
+{
+    type Target = JwtDataContainer<T>;
+
+    fn deref(&self) -> &JwtDataContainer<T> {
+        &self.0
+    }
+}
+
+impl<T> FromRequest for Authenticated<T>
+where
+    T: Serialize + DeserializeOwned + Send + Sync + 'static,
+{
+    type Error = AuthError;
+
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        if let Some(VerifiedClaims(claims)) = req.extensions().get::<VerifiedClaims>().cloned() {
+            return Box::pin(async { claims.try_map(from_value).map(Authenticated) });
+        }
+
+        let codec = app_token_codec(req);
+        let token = bearer_token(req).map(str::to_string);
+
+        Box::pin(async move {
+            let codec = codec.ok_or(AuthError::InternalError)?;
+            let token = token.ok_or(AuthError::MissingToken)?;
+
+            codec
+                .verify_token_async::<T>(&token)
+                .await
+                .map(Authenticated)
+        })
+    }
+}
+
+/// The claims of the `Bearer` token of a request, once verified by
+/// [`RequireAuthorization`](crate::auth::middleware::authorization::RequireAuthorization).
+#[derive(Clone)]
+pub(crate) struct VerifiedClaims(pub(crate) JwtDataContainer<Value>);
+
+/// Reads the token out of an `Authorization: Bearer <token>` header.
+pub(crate) fn bearer_token(req: &HttpRequest) -> Option<&str> {
+    let header = req.headers().get(AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, token) = header.split_once(' ')?;
+
+    if !scheme.eq_ignore_ascii_case("Bearer") {
+        return None;
+    }
+
+    Some(token.trim()).filter(|token| !token.is_empty())
+}
+
+#[cfg(all(test, any(feature = "jwt", feature = "paseto")))]
+mod tests {
+    use super::*;
+    use crate::auth::service::clock::{Clock, MockClock};
+    #[cfg(feature = "jwt")]
+    use crate::auth::service::jwt_key::JwtKey;
+    #[cfg(feature = "jwt")]
+    use crate::auth::service::jwt_service::JwtService;
+    #[cfg(feature = "paseto")]
+    use crate::auth::service::paseto_key::PasetoKey;
+    #[cfg(feature = "paseto")]
+    use crate::auth::service::paseto_service::PasetoService;
+    use crate::auth::service::token_codec::TokenCodec;
+    use crate::auth::test_keys::*;
+    use actix_web::http::StatusCode;
+    use actix_web::http::header::WWW_AUTHENTICATE;
+    use actix_web::{App, HttpResponse, test, web};
+    #[cfg(feature = "jwt")]
+    use jsonwebtoken::Algorithm;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    async fn me(user: Authenticated<String>) -> HttpResponse {
+        HttpResponse::Ok().body(user.into_inner().data)
+    }
+
+    async fn greeting(user: Option<Authenticated<String>>) -> HttpResponse {
+        match user {
+            Some(user) => HttpResponse::Ok().body(user.data.clone()),
+            None => HttpResponse::Ok().body("anonymous"),
+        }
+    }
+
+    #[cfg(feature = "jwt")]
+    fn jwt_service(clock: Arc<MockClock>) -> web::Data<JwtService> {
+        web::Data::new(
+            JwtService::with_key(JwtKey::hmac(Algorithm::HS256, HMAC_TEST_SECRET).unwrap())
+                .with_clock(clock),
+        )
+    }
+
+    /// Runs the extractor against whichever codec the app registers.
+    async fn check_authenticated(codec: Arc<dyn TokenCodec>, clock: Arc<MockClock>) {
+        let token = codec
+            .generate_token("lunna".to_string(), clock.now() + 60)
+            .unwrap();
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::from(codec))
+                .route("/me", web::get().to(me))
+                .route("/greeting", web::get().to(greeting)),
+        )
+        .await;
+
+        let request = |uri: &str, authorization: Option<String>| {
+            let mut request = test::TestRequest::get().uri(uri);
+            if let Some(authorization) = authorization {
+                request = request.insert_header((AUTHORIZATION, authorization));
+            }
+            request.to_request()
+        };
+
+        let response =
+            test::call_service(&app, request("/me", Some(format!("Bearer {token}")))).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(test::read_body(response).await, "lunna");
+
+        let response = test::call_service(&app, request("/me", None)).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(response.headers().get(WWW_AUTHENTICATE).unwrap(), "Bearer");
+
+        let response =
+            test::call_service(&app, request("/me", Some("Bearer not.a.token".to_string()))).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert!(
+            response
+                .headers()
+                .get(WWW_AUTHENTICATE)
+                .unwrap()
+                .to_str()
+                .unwrap()
+                .starts_with(r#"Bearer error="invalid_token""#)
+        );
+        let body: serde_json::Value = test::read_body_json(response).await;
+        assert_eq!(body["key"], "auth.invalid_token");
+
+        let response =
+            test::call_service(&app, request("/greeting", Some(format!("Bearer {token}")))).await;
+        assert_eq!(test::read_body(response).await, "lunna");
+        let response = test::call_service(&app, request("/greeting", None)).await;
+        assert_eq!(test::read_body(response).await, "anonymous");
+
+        clock.advance(Duration::from_secs(61));
+        let response =
+            test::call_service(&app, request("/me", Some(format!("Bearer {token}")))).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let body: serde_json::Value = test::read_body_json(response).await;
+        assert_eq!(body["key"], "auth.token_expired");
+    }
+
+    #[cfg(feature = "jwt")]
+    #[actix_web::test]
+    async fn test_authenticated() {
+        let clock = Arc::new(MockClock::new(1_700_000_000));
+        check_authenticated(jwt_service(clock.clone()).into_inner(), clock).await;
+    }
+
+    #[cfg(feature = "paseto")]
+    #[actix_web::test]
+    async fn test_authenticated_with_paseto() {
+        let clock = Arc::new(MockClock::new(1_700_000_000));
+        let key = PasetoKey::public(
+            Some(ED25519_PRIVATE_TEST_KEY.to_string()),
+            ED25519_PUBLIC_TEST_KEY.to_string(),
+        )
+        .unwrap();
+        let codec = Arc::new(PasetoService::new(key).with_clock(clock.clone()));
+        check_authenticated(codec, clock).await;
+    }
+
+    #[cfg(feature = "jwt")]
+    #[actix_web::test]
+    async fn test_jwt_service_data() {
+        let clock = Arc::new(MockClock::new(1_700_000_000));
+        let jwt_service = jwt_service(clock.clone());
+        let token = jwt_service
+            .generate_token("lunna".to_string(), clock.now() + 60)
+            .unwrap();
+        let app = test::init_service(
+            App::new()
+                .app_data(jwt_service)
+                .route("/me", web::get().to(me)),
+        )
+        .await;
+
+        let request = test::TestRequest::get()
+            .uri("/me")
+            .insert_header((AUTHORIZATION, format!("Bearer {token}")))
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(test::read_body(response).await, "lunna");
+    }
+
+    #[actix_web::test]
+    async fn test_no_token_codec() {
+        let app = test::init_service(App::new().route("/me", web::get().to(me))).await;
+
+        let request = test::TestRequest::get()
+            .uri("/me")
+            .insert_header((AUTHORIZATION, "Bearer abc"))
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    #[actix_web::test]
+    async fn test_bearer_token() {
+        let request = |value: &str| {
+            test::TestRequest::default()
+                .insert_header((AUTHORIZATION, value))
+                .to_http_request()
+        };
+
+        assert_eq!(bearer_token(&request("Bearer abc")), Some("abc"));
+        assert_eq!(bearer_token(&request("bearer abc")), Some("abc"));
+        assert_eq!(bearer_token(&request("Basic abc")), None);
+        assert_eq!(bearer_token(&request("Bearer ")), None);
+        assert_eq!(
+            bearer_token(&test::TestRequest::default().to_http_request()),
+            None
+        );
+    }
+}
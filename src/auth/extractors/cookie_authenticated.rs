@@ -0,0 +1,227 @@
+use crate::auth::error::AuthError;
+use crate::auth::service::token_claims::JwtDataContainer;
+use crate::auth::service::token_codec::app_token_codec;
+use crate::auth::service::token_cookie_config::TokenCookieConfig;
+use actix_web::{FromRequest, HttpRequest, dev::Payload};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::future::{Future, Ready, ready};
+use std::ops;
+use std::pin::Pin;
+
+/// The verified claims of the short token cookie sent with a request.
+///
+/// The cookie counterpart of [`Authenticated`](super::authenticated::Authenticated),
+/// for tokens set with
+/// [`TokenCookieResponse`](crate::auth::response::token_cookie_response::TokenCookieResponse).
+/// Cookie names come from the `web::Data<TokenCookieConfig>` of the app, or the
+/// default one. State-changing requests (anything but `GET`, `HEAD`, `OPTIONS` and
+/// `TRACE`) must also echo the CSRF cookie in the CSRF header, or they are rejected
+/// with [`AuthError::InvalidCsrfToken`] and `403 Forbidden`.
+///
+/// # Example
+/// ```
+/// use actix_web::post;
+/// use lunna_actix_utils::auth::extractors::cookie_authenticated::CookieAuthenticated;
+///
+/// #[post("/orders")]
+/// async fn create_order(user: CookieAuthenticated<String>) -> String {
+///     format!("Order placed for {}", user.data)
+/// }
+/// ```
+pub struct CookieAuthenticated<T>(pub JwtDataContainer<T>)
+where
+    T: Serialize + DeserializeOwned;
+
+impl<T> CookieAuthenticated<T>
+where
+    T: Serialize + DeserializeOwned,
+{
+    /// Consumes the wrapper and returns the claims.
+    pub fn into_inner(self) -> JwtDataContainer<T> {
+        self.0
+    }
+}
+
+impl<T> ops::Deref for CookieAuthenticated<T>
+where
+    T: Serialize + DeserializeOwned,
+{
+    type Target = JwtDataContainer<T>;
+
+    fn deref(&self) -> &JwtDataContainer<T> {
+        &self.0
+    }
+}
+
+impl<T> FromRequest for CookieAuthenticated<T>
+where
+    T: Serialize + DeserializeOwned + Send + Sync + 'static,
+{
+    type Error = AuthError;
+
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let codec = app_token_codec(req);
+        let config = TokenCookieConfig::of(req);
+        let token = config
+            .verify_csrf(req)
+            .map(|_| config.token(req, &config.short_token));
+
+        Box::pin(async move {
+            let codec = codec.ok_or(AuthError::InternalError)?;
+            let token = token?.ok_or(AuthError::MissingToken)?;
+
+            codec
+                .verify_token_async::<T>(&token)
+                .await
+                .map(CookieAuthenticated)
+        })
+    }
+}
+
+/// The long token cookie sent with a request, for the renew and logout endpoints.
+///
+/// The token is opaque here and still has to be checked, for instance with
+/// [`RefreshTokenService::rotate`](crate::auth::service::refresh_token_service::RefreshTokenService::rotate).
+/// The CSRF check is the same as for [`CookieAuthenticated`].
+///
+/// # Example
+/// ```
+/// use actix_web::post;
+/// use lunna_actix_utils::auth::extractors::cookie_authenticated::LongTokenCookie;
+/// use lunna_actix_utils::auth::response::token_cookie_response::TokenCookieResponse;
+///
+/// #[post("/logout")]
+/// async fn logout(long_token: LongTokenCookie) -> TokenCookieResponse {
+///     // Revoke `long_token.0` here.
+///     TokenCookieResponse::clear()
+/// }
+/// ```
+pub struct LongTokenCookie(pub String);
+
+impl FromRequest for LongTokenCookie {
+    type Error = AuthError;
+
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let config = TokenCookieConfig::of(req);
+
+        ready(config.verify_csrf(req).and_then(|_| {
+            config
+                .token(req, &config.long_token)
+                .map(LongTokenCookie)
+                .ok_or(AuthError::MissingToken)
+        }))
+    }
+}
+
+#[cfg(all(test, feature = "jwt"))]
+mod tests {
+    use super::*;
+    use crate::auth::response::token_cookie_response::TokenCookieResponse;
+    use crate::auth::response::token_response::TokenResponse;
+    use crate::auth::service::clock::{Clock, MockClock};
+    use crate::auth::service::jwt_key::JwtKey;
+    use crate::auth::service::jwt_service::JwtService;
+    use crate::auth::test_keys::*;
+    use actix_web::cookie::Cookie;
+    use actix_web::http::StatusCode;
+    use actix_web::{App, HttpResponse, test, web};
+    use jsonwebtoken::Algorithm;
+    use std::sync::Arc;
+
+    async fn me(user: CookieAuthenticated<String>) -> HttpResponse {
+        HttpResponse::Ok().body(user.into_inner().data)
+    }
+
+    async fn renew(long_token: LongTokenCookie) -> HttpResponse {
+        HttpResponse::Ok().body(long_token.0)
+    }
+
+    #[actix_web::test]
+    async fn test_cookie_authenticated() {
+        let clock = Arc::new(MockClock::new(1_700_000_000));
+        let jwt_service = web::Data::new(
+            JwtService::with_key(JwtKey::hmac(Algorithm::HS256, HMAC_TEST_SECRET).unwrap())
+                .with_clock(clock.clone()),
+        );
+        let short_token = jwt_service
+            .generate_token("lunna".to_string(), clock.now() + 60)
+            .unwrap();
+        let tokens = TokenResponse {
+            long_token: Some("long".to_string()),
+            short_token,
+        };
+
+        let app = test::init_service(
+            App::new()
+                .app_data(jwt_service)
+                .route(
+                    "/login",
+                    web::post().to(move || {
+                        let tokens = tokens.clone();
+                        async move { TokenCookieResponse::new(tokens) }
+                    }),
+                )
+                .route("/me", web::get().to(me))
+                .route("/me", web::post().to(me))
+                .route("/renew", web::post().to(renew)),
+        )
+        .await;
+
+        let response =
+            test::call_service(&app, test::TestRequest::post().uri("/login").to_request()).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let cookies: Vec<Cookie<'static>> = response
+            .response()
+            .cookies()
+            .map(|cookie| cookie.into_owned())
+            .collect();
+        let csrf_token = cookies
+            .iter()
+            .find(|cookie| cookie.name() == "csrf_token")
+            .unwrap()
+            .value()
+            .to_string();
+
+        let request = |request: test::TestRequest| {
+            cookies
+                .iter()
+                .fold(request, |request, cookie| request.cookie(cookie.clone()))
+        };
+
+        let response = test::call_service(
+            &app,
+            request(test::TestRequest::get().uri("/me")).to_request(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(test::read_body(response).await, "lunna");
+
+        let response = test::call_service(
+            &app,
+            request(test::TestRequest::post().uri("/me")).to_request(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let body: serde_json::Value = test::read_body_json(response).await;
+        assert_eq!(body["key"], "auth.invalid_csrf_token");
+
+        let response = test::call_service(
+            &app,
+            request(test::TestRequest::post().uri("/renew"))
+                .insert_header(("X-CSRF-Token", csrf_token))
+                .to_request(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(test::read_body(response).await, "long");
+
+        let response =
+            test::call_service(&app, test::TestRequest::get().uri("/me").to_request()).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+}
@@ -0,0 +1,164 @@
+use crate::auth::error::AuthError;
+use crate::auth::extractors::authenticated::{Authenticated, bearer_token};
+use crate::auth::service::token_claims::JwtDataContainer;
+use actix_web::{FromRequest, HttpRequest, dev::Payload};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::future::Future;
+use std::ops;
+use std::pin::Pin;
+
+/// The verified claims of the `Bearer` token sent with a request, if any.
+///
+/// For endpoints where authentication is optional. Unlike `Option<Authenticated<T>>`,
+/// which turns every error into `None`, only a request without a `Bearer` token gets
+/// `None`: an invalid, expired or revoked token is still rejected like
+/// [`Authenticated`] does, and so is a missing token codec.
+///
+/// # Example
+/// ```
+/// use actix_web::get;
+/// use lunna_actix_utils::auth::extractors::maybe_authenticated::MaybeAuthenticated;
+///
+/// #[get("/greeting")]
+/// async fn greeting(user: MaybeAuthenticated<String>) -> String {
+///     match user.into_inner() {
+///         Some(user) => format!("Welcome back, {}!", user.data),
+///         None => "Welcome!".to_string(),
+///     }
+/// }
+/// ```
+pub struct MaybeAuthenticated<T>(pub Option<JwtDataContainer<T>>)
+where
+    T: Serialize + DeserializeOwned;
+
+impl<T> MaybeAuthenticated<T>
+where
+    T: Serialize + DeserializeOwned,
+{
+    /// Consumes the wrapper and returns the claims, `None` if no token was sent.
+    pub fn into_inner(self) -> Option<JwtDataContainer<T>> {
+        self.0
+    }
+}
+
+impl<T> ops::Deref for MaybeAuthenticated<T>
+where
+    T: Serialize + DeserializeOwned,
+{
+    type Target = Option<JwtDataContainer<T>>;
+
+    fn deref(&self) -> &Option<JwtDataContainer<T>> {
+        &self.0
+    }
+}
+
+impl<T> FromRequest for MaybeAuthenticated<T>
+where
+    T: Serialize + DeserializeOwned + Send + Sync + 'static,
+{
+    type Error = AuthError;
+
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        if bearer_token(req).is_none() {
+            return Box::pin(async { Ok(MaybeAuthenticated(None)) });
+        }
+
+        let authenticated = Authenticated::<T>::from_request(req, payload);
+
+        Box::pin(async move {
+            authenticated
+                .await
+                .map(|authenticated| MaybeAuthenticated(Some(authenticated.into_inner())))
+        })
+    }
+}
+
+#[cfg(all(test, feature = "jwt"))]
+mod tests {
+    use super::*;
+    use crate::auth::service::clock::{Clock, MockClock};
+    use crate::auth::service::jwt_key::JwtKey;
+    use crate::auth::service::jwt_service::JwtService;
+    use crate::auth::test_keys::*;
+    use actix_web::http::StatusCode;
+    use actix_web::http::header::AUTHORIZATION;
+    use actix_web::{App, HttpResponse, test, web};
+    use jsonwebtoken::Algorithm;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    async fn greeting(user: MaybeAuthenticated<String>) -> HttpResponse {
+        match user.into_inner() {
+            Some(user) => HttpResponse::Ok().body(user.data),
+            None => HttpResponse::Ok().body("anonymous"),
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_maybe_authenticated() {
+        let clock = Arc::new(MockClock::new(1_700_000_000));
+        let jwt_service = web::Data::new(
+            JwtService::with_key(JwtKey::hmac(Algorithm::HS256, HMAC_TEST_SECRET).unwrap())
+                .with_clock(clock.clone()),
+        );
+        let token = jwt_service
+            .generate_token("lunna".to_string(), clock.now() + 60)
+            .unwrap();
+        let app = test::init_service(
+            App::new()
+                .app_data(jwt_service)
+                .route("/greeting", web::get().to(greeting)),
+        )
+        .await;
+
+        let request = |authorization: Option<String>| {
+            let mut request = test::TestRequest::get().uri("/greeting");
+            if let Some(authorization) = authorization {
+                request = request.insert_header((AUTHORIZATION, authorization));
+            }
+            request.to_request()
+        };
+
+        let response = test::call_service(&app, request(Some(format!("Bearer {token}")))).await;
+        assert_eq!(test::read_body(response).await, "lunna");
+
+        for authorization in [None, Some("Basic abc".to_string())] {
+            let response = test::call_service(&app, request(authorization)).await;
+            assert_eq!(test::read_body(response).await, "anonymous");
+        }
+
+        let response =
+            test::call_service(&app, request(Some("Bearer not.a.token".to_string()))).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let body: serde_json::Value = test::read_body_json(response).await;
+        assert_eq!(body["key"], "auth.invalid_token");
+
+        clock.advance(Duration::from_secs(61));
+        let response = test::call_service(&app, request(Some(format!("Bearer {token}")))).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let body: serde_json::Value = test::read_body_json(response).await;
+        assert_eq!(body["key"], "auth.token_expired");
+    }
+
+    #[actix_web::test]
+    async fn test_maybe_authenticated_without_codec() {
+        let app = test::init_service(App::new().route("/greeting", web::get().to(greeting))).await;
+
+        let response =
+            test::call_service(&app, test::TestRequest::get().uri("/greeting").to_request()).await;
+        assert_eq!(test::read_body(response).await, "anonymous");
+
+        let response = test::call_service(
+            &app,
+            test::TestRequest::get()
+                .uri("/greeting")
+                .insert_header((AUTHORIZATION, "Bearer abc"))
+                .to_request(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+}
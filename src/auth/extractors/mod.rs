@@ -0,0 +1,3 @@
+pub mod authenticated;
+pub mod cookie_authenticated;
+pub mod maybe_authenticated;
@@ -0,0 +1,255 @@
+use crate::auth::error::AuthError;
+use crate::auth::request::introspection_request::{IntrospectionRequest, IntrospectionRequestLike};
+use crate::auth::response::introspection_response::IntrospectionResponse;
+use crate::auth::service::client_credentials::{ClientAuthenticator, ClientCredentials};
+use crate::auth::service::token_codec::app_token_codec;
+use actix_web::http::header::{CacheControl, CacheDirective};
+use actix_web::{HttpRequest, HttpResponse, Scope, web};
+
+/// Tells an authenticated client whether a token is active (RFC 7662).
+///
+/// The token is checked like any protected route would: signature, registered claims
+/// and the revocation store of the app's
+/// [`TokenCodec`](crate::auth::service::token_codec::TokenCodec). Any token error
+/// answers `{"active": false}`.
+///
+/// The caller authenticates with HTTP Basic or with `client_id` and `client_secret`
+/// in the form, checked by a `web::Data<ClientAuthenticator>`.
+#[utoipa::path(
+    post,
+    path = "/oauth2/introspect",
+    tag = "auth",
+    request_body(content = IntrospectionRequest, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "The state of the token", body = IntrospectionResponse),
+        (status = 401, description = "Invalid client credentials")
+    )
+)]
+pub async fn introspect(
+    req: HttpRequest,
+    form: web::Form<IntrospectionRequest>,
+    authenticator: web::Data<ClientAuthenticator>,
+) -> Result<HttpResponse, AuthError> {
+    let credentials =
+        ClientCredentials::from_request(&req, form.client_id(), form.client_secret())?;
+    authenticator.authenticate(&credentials).await?;
+
+    let codec = app_token_codec(&req).ok_or(AuthError::InternalError)?;
+    let response = match codec.decode_async(form.token()).await {
+        Ok(container) => IntrospectionResponse::from(container),
+        Err(
+            AuthError::InvalidToken
+            | AuthError::TokenExpired
+            | AuthError::TokenNotFound
+            | AuthError::TokenNotValid
+            | AuthError::TokenRevoked
+            | AuthError::TokenReused,
+        ) => IntrospectionResponse::inactive(),
+        Err(err) => return Err(err),
+    };
+
+    Ok(HttpResponse::Ok()
+        .insert_header(CacheControl(vec![CacheDirective::NoStore]))
+        .json(response))
+}
+
+/// An `/oauth2` scope serving [`introspect`] at `/oauth2/introspect`.
+///
+/// # Example
+/// ```
+/// # #[cfg(feature = "jwt")]
+/// # fn main() {
+/// use actix_web::{App, web};
+/// use lunna_actix_utils::auth::handler::introspection_handler::introspection_scope;
+/// use lunna_actix_utils::auth::service::client_credentials::{
+///     ClientAuthenticator, InMemoryClientStore,
+/// };
+/// use lunna_actix_utils::auth::service::hash_service::HashService;
+/// use lunna_actix_utils::auth::service::jwt_service::JwtService;
+/// use std::sync::Arc;
+///
+/// # let public_key =
+/// #     include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/rsa_public.pem"));
+/// let hash_service = HashService::new();
+/// let gateway_secret_hash = hash_service.hash_password("gateway secret").unwrap();
+/// let clients = InMemoryClientStore::new().with_client("gateway", &gateway_secret_hash);
+/// let authenticator = ClientAuthenticator::new(Arc::new(clients), hash_service);
+/// let jwt_service = JwtService::new_without_private(public_key.to_string()).unwrap();
+///
+/// let app = App::new()
+///     .app_data(web::Data::new(jwt_service))
+///     .app_data(web::Data::new(authenticator))
+///     .service(introspection_scope());
+/// # }
+/// # #[cfg(not(feature = "jwt"))]
+/// # fn main() {}
+/// ```
+pub fn introspection_scope() -> Scope {
+    web::scope("/oauth2").route("/introspect", web::post().to(introspect))
+}
+
+#[cfg(all(test, feature = "jwt"))]
+mod tests {
+    use super::*;
+    use crate::auth::service::client_credentials::InMemoryClientStore;
+    use crate::auth::service::hash_service::HashService;
+    use crate::auth::service::jwt_key::JwtKey;
+    use crate::auth::service::jwt_service::{JwtDataContainer, JwtService, get_current_time};
+    use crate::auth::service::revocation_store::InMemoryRevocationStore;
+    use crate::auth::test_keys::HMAC_TEST_SECRET;
+    use actix_web::http::{StatusCode, header};
+    use actix_web::{App, test};
+    use base64::Engine;
+    use base64::engine::general_purpose::STANDARD;
+    use jsonwebtoken::Algorithm;
+    use serde_json::{Value, json};
+    use std::sync::Arc;
+
+    fn services() -> (web::Data<JwtService>, web::Data<ClientAuthenticator>) {
+        let jwt_service = web::Data::new(
+            JwtService::with_key(JwtKey::hmac(Algorithm::HS256, HMAC_TEST_SECRET).unwrap())
+                .with_revocation_store(Arc::new(InMemoryRevocationStore::new())),
+        );
+        let hash_service = HashService::new();
+        let hash = hash_service.hash_password("gateway secret").unwrap();
+        let authenticator = ClientAuthenticator::new(
+            Arc::new(InMemoryClientStore::new().with_client("gateway", &hash)),
+            hash_service,
+        );
+
+        (jwt_service, web::Data::new(authenticator))
+    }
+
+    fn request(token: &str) -> test::TestRequest {
+        test::TestRequest::post()
+            .uri("/oauth2/introspect")
+            .insert_header((
+                header::AUTHORIZATION,
+                format!("Basic {}", STANDARD.encode("gateway:gateway secret")),
+            ))
+            .set_form([("token", token)])
+    }
+
+    #[actix_web::test]
+    async fn test_active_token() {
+        let (jwt_service, authenticator) = services();
+        let app = test::init_service(
+            App::new()
+                .app_data(jwt_service.clone())
+                .app_data(authenticator)
+                .service(introspection_scope()),
+        )
+        .await;
+        let token = jwt_service
+            .generate_token_with_data_container(
+                JwtDataContainer::new(json!({"username": "lunna"}), get_current_time() + 60)
+                    .with_subject("42"),
+            )
+            .unwrap();
+
+        let response = test::call_service(&app, request(&token).to_request()).await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers().get(header::CACHE_CONTROL).unwrap(),
+            "no-store"
+        );
+
+        let body: Value = test::read_body_json(response).await;
+        assert_eq!(body["active"], true);
+        assert_eq!(body["username"], "lunna");
+        assert_eq!(body["sub"], "42");
+        assert_eq!(body["token_type"], "Bearer");
+    }
+
+    #[actix_web::test]
+    async fn test_revoked_and_invalid_tokens_are_inactive() {
+        let (jwt_service, authenticator) = services();
+        let app = test::init_service(
+            App::new()
+                .app_data(jwt_service.clone())
+                .app_data(authenticator)
+                .service(introspection_scope()),
+        )
+        .await;
+        let token = jwt_service
+            .generate_token("lunna".to_string(), get_current_time() + 60)
+            .unwrap();
+        jwt_service.revoke_token(&token).await.unwrap();
+
+        for token in [token.as_str(), "not a token"] {
+            let response = test::call_service(&app, request(token).to_request()).await;
+            assert_eq!(response.status(), StatusCode::OK);
+
+            let body: Value = test::read_body_json(response).await;
+            assert_eq!(body, json!({"active": false}));
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_client_credentials_in_the_form() {
+        let (jwt_service, authenticator) = services();
+        let app = test::init_service(
+            App::new()
+                .app_data(jwt_service.clone())
+                .app_data(authenticator)
+                .service(introspection_scope()),
+        )
+        .await;
+        let token = jwt_service
+            .generate_token("lunna".to_string(), get_current_time() + 60)
+            .unwrap();
+
+        let response = test::call_service(
+            &app,
+            test::TestRequest::post()
+                .uri("/oauth2/introspect")
+                .set_form([
+                    ("token", token.as_str()),
+                    ("client_id", "gateway"),
+                    ("client_secret", "gateway secret"),
+                ])
+                .to_request(),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn test_unauthenticated_client_is_rejected() {
+        let (jwt_service, authenticator) = services();
+        let app = test::init_service(
+            App::new()
+                .app_data(jwt_service.clone())
+                .app_data(authenticator)
+                .service(introspection_scope()),
+        )
+        .await;
+        let token = jwt_service
+            .generate_token("lunna".to_string(), get_current_time() + 60)
+            .unwrap();
+
+        let response = test::call_service(
+            &app,
+            test::TestRequest::post()
+                .uri("/oauth2/introspect")
+                .set_form([
+                    ("token", token.as_str()),
+                    ("client_id", "gateway"),
+                    ("client_secret", "wrong"),
+                ])
+                .to_request(),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            response.headers().get(header::WWW_AUTHENTICATE).unwrap(),
+            "Basic"
+        );
+
+        let body: Value = test::read_body_json(response).await;
+        assert_eq!(body["key"], "auth.invalid_client");
+    }
+}
@@ -0,0 +1,126 @@
+use crate::auth::response::jwks_response::JwksResponse;
+use crate::auth::service::jwt_service::JwtService;
+use actix_web::http::header::{CacheControl, CacheDirective};
+use actix_web::{HttpResponse, Scope, web};
+
+/// How long clients may cache the key set. Short enough that a newly added key is
+/// picked up well before it is promoted, if keys are added a few minutes ahead.
+pub const JWKS_MAX_AGE: u32 = 300;
+
+/// Publishes the verification keys of the [`JwtService`] as a JWKS document.
+///
+/// Every non-retired asymmetric key is listed with its `kid`, `alg` and `use`.
+/// HMAC secrets are never published. Expects the service as `web::Data<JwtService>`.
+#[utoipa::path(
+    get,
+    path = "/.well-known/jwks.json",
+    tag = "auth",
+    responses(
+        (status = 200, description = "The public keys used to verify tokens", body = JwksResponse)
+    )
+)]
+pub async fn jwks(jwt_service: web::Data<JwtService>) -> HttpResponse {
+    HttpResponse::Ok()
+        .insert_header(CacheControl(vec![
+            CacheDirective::Public,
+            CacheDirective::MaxAge(JWKS_MAX_AGE),
+        ]))
+        .json(JwksResponse::from(jwt_service.jwk_set()))
+}
+
+/// A `/.well-known` scope serving [`jwks`] at `/.well-known/jwks.json`.
+///
+/// # Example
+/// ```
+/// use actix_web::{App, web};
+/// use lunna_actix_utils::auth::handler::jwks_handler::jwks_scope;
+/// use lunna_actix_utils::auth::service::jwt_service::JwtService;
+///
+/// # let public_key =
+/// #     include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/rsa_public.pem"));
+/// let jwt_service =
+///     web::Data::new(JwtService::new_without_private(public_key.to_string()).unwrap());
+/// let app = App::new().app_data(jwt_service).service(jwks_scope());
+/// ```
+pub fn jwks_scope() -> Scope {
+    web::scope("/.well-known").route("/jwks.json", web::get().to(jwks))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::service::jwt_key::JwtKey;
+    use crate::auth::service::jwt_keyring::JwtKeyring;
+    use crate::auth::service::jwt_service::get_current_time;
+    use crate::auth::test_keys::*;
+    use actix_web::{App, http::header, test};
+    use jsonwebtoken::jwk::JwkSet;
+    use jsonwebtoken::{DecodingKey, Validation, decode, decode_header};
+    use serde_json::Value;
+
+    #[actix_web::test]
+    async fn test_jwks_publishes_verification_keys() {
+        let keyring = JwtKeyring::new(
+            "rsa",
+            JwtKey::rsa(
+                jsonwebtoken::Algorithm::RS256,
+                Some(RSA_PRIVATE_TEST_KEY.to_string()),
+                RSA_PUBLIC_TEST_KEY.to_string(),
+            )
+            .unwrap(),
+        );
+        keyring
+            .add(
+                "ec",
+                JwtKey::ec(
+                    jsonwebtoken::Algorithm::ES256,
+                    None,
+                    EC_PUBLIC_TEST_KEY.to_string(),
+                )
+                .unwrap(),
+            )
+            .unwrap();
+        keyring
+            .add(
+                "hmac",
+                JwtKey::hmac(jsonwebtoken::Algorithm::HS256, HMAC_TEST_SECRET).unwrap(),
+            )
+            .unwrap();
+        let jwt_service = web::Data::new(JwtService::with_keyring(keyring));
+        let token = jwt_service
+            .generate_token("payload".to_string(), get_current_time() + 60)
+            .unwrap();
+
+        let app = test::init_service(App::new().app_data(jwt_service).service(jwks_scope())).await;
+        let response = test::call_service(
+            &app,
+            test::TestRequest::get()
+                .uri("/.well-known/jwks.json")
+                .to_request(),
+        )
+        .await;
+
+        assert!(response.status().is_success());
+        assert_eq!(
+            response.headers().get(header::CACHE_CONTROL).unwrap(),
+            "public, max-age=300"
+        );
+
+        let body: Value = test::read_body_json(response).await;
+        let keys = body["keys"].as_array().unwrap();
+        assert_eq!(keys.len(), 2);
+        assert_eq!(keys[0]["kid"], "rsa");
+        assert_eq!(keys[0]["alg"], "RS256");
+        assert_eq!(keys[0]["use"], "sig");
+        assert_eq!(keys[1]["kid"], "ec");
+        assert_eq!(keys[1]["crv"], "P-256");
+
+        let set: JwkSet = serde_json::from_value(body).unwrap();
+        let kid = decode_header(&token).unwrap().kid.unwrap();
+        let jwk = set.find(&kid).unwrap();
+        let decoding_key = DecodingKey::from_jwk(jwk).unwrap();
+        let validation = Validation::new(jsonwebtoken::Algorithm::RS256);
+
+        assert!(decode::<Value>(&token, &decoding_key, &validation).is_ok());
+    }
+}
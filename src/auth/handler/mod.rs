@@ -0,0 +1,4 @@
+pub mod introspection_handler;
+#[cfg(feature = "jwt")]
+pub mod jwks_handler;
+pub mod token_exchange_handler;
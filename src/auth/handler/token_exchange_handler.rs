@@ -0,0 +1,288 @@
+use crate::auth::error::AuthError;
+use crate::auth::handler::introspection_handler::introspect;
+use crate::auth::request::token_exchange_request::{
+    ACCESS_TOKEN_TYPE, JWT_TOKEN_TYPE, TOKEN_EXCHANGE_GRANT_TYPE, TokenExchangeRequest,
+    TokenExchangeRequestLike,
+};
+use crate::auth::response::token_exchange_response::TokenExchangeResponse;
+use crate::auth::service::client_credentials::{ClientAuthenticator, ClientCredentials};
+use crate::auth::service::token_codec::app_token_codec;
+use crate::auth::service::token_exchange_service::TokenExchangeService;
+use actix_web::http::header::{CacheControl, CacheDirective};
+use actix_web::{HttpRequest, HttpResponse, Scope, web};
+
+/// Exchanges a subject token for a token scoped to one downstream service (RFC 8693).
+///
+/// The caller authenticates like on the introspection endpoint, with a
+/// `web::Data<ClientAuthenticator>`, and is recorded as the `act` of the issued token.
+/// The narrowing rules are those of a `web::Data<TokenExchangeService>`, and tokens
+/// are checked and issued with the app's
+/// [`TokenCodec`](crate::auth::service::token_codec::TokenCodec).
+#[utoipa::path(
+    post,
+    path = "/oauth2/token",
+    tag = "auth",
+    request_body(content = TokenExchangeRequest, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "The issued token", body = TokenExchangeResponse),
+        (status = 400, description = "Unsupported grant or token type, invalid subject token, scope or audience"),
+        (status = 401, description = "Invalid client credentials")
+    )
+)]
+pub async fn token_exchange(
+    req: HttpRequest,
+    form: web::Form<TokenExchangeRequest>,
+    authenticator: web::Data<ClientAuthenticator>,
+    exchange: web::Data<TokenExchangeService>,
+) -> Result<HttpResponse, AuthError> {
+    let credentials =
+        ClientCredentials::from_request(&req, form.client_id(), form.client_secret())?;
+    authenticator.authenticate(&credentials).await?;
+
+    if form.grant_type() != TOKEN_EXCHANGE_GRANT_TYPE {
+        return Err(AuthError::UnsupportedGrantType);
+    }
+    if ![ACCESS_TOKEN_TYPE, JWT_TOKEN_TYPE].contains(&form.subject_token_type())
+        || form
+            .requested_token_type()
+            .is_some_and(|token_type| token_type != ACCESS_TOKEN_TYPE)
+    {
+        return Err(AuthError::UnsupportedTokenType);
+    }
+    let audience = form.audience().ok_or(AuthError::InvalidTarget)?;
+
+    let codec = app_token_codec(&req).ok_or(AuthError::InternalError)?;
+    let exchanged = exchange
+        .exchange(
+            codec.as_ref(),
+            form.subject_token(),
+            &credentials.client_id,
+            audience,
+            form.scope(),
+        )
+        .await?;
+
+    Ok(HttpResponse::Ok()
+        .insert_header(CacheControl(vec![CacheDirective::NoStore]))
+        .json(TokenExchangeResponse::from(exchanged)))
+}
+
+/// An `/oauth2` scope serving [`token_exchange`] at `/oauth2/token` and
+/// [`introspect`] at `/oauth2/introspect`.
+///
+/// Use it instead of
+/// [`introspection_scope`](super::introspection_handler::introspection_scope) when
+/// both are served, as two `/oauth2` scopes would shadow each other.
+///
+/// # Example
+/// ```
+/// # #[cfg(feature = "jwt")]
+/// # fn main() {
+/// use actix_web::{App, web};
+/// use lunna_actix_utils::auth::handler::token_exchange_handler::oauth2_scope;
+/// use lunna_actix_utils::auth::service::client_credentials::{
+///     ClientAuthenticator, InMemoryClientStore,
+/// };
+/// use lunna_actix_utils::auth::service::hash_service::HashService;
+/// use lunna_actix_utils::auth::service::jwt_service::JwtService;
+/// use lunna_actix_utils::auth::service::token_exchange_service::TokenExchangeService;
+/// use std::sync::Arc;
+///
+/// # let private_key =
+/// #     include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/rsa_private.pem"));
+/// # let public_key =
+/// #     include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/rsa_public.pem"));
+/// let hash_service = HashService::new();
+/// let edge_secret_hash = hash_service.hash_password("edge secret").unwrap();
+/// let clients = InMemoryClientStore::new().with_client("edge", &edge_secret_hash);
+/// let authenticator = ClientAuthenticator::new(Arc::new(clients), hash_service);
+/// let exchange = TokenExchangeService::new().allow_audience("edge", "billing");
+/// let jwt_service =
+///     JwtService::new(private_key.to_string(), public_key.to_string()).unwrap();
+///
+/// let app = App::new()
+///     .app_data(web::Data::new(jwt_service))
+///     .app_data(web::Data::new(authenticator))
+///     .app_data(web::Data::new(exchange))
+///     .service(oauth2_scope());
+/// # }
+/// # #[cfg(not(feature = "jwt"))]
+/// # fn main() {}
+/// ```
+pub fn oauth2_scope() -> Scope {
+    web::scope("/oauth2")
+        .route("/token", web::post().to(token_exchange))
+        .route("/introspect", web::post().to(introspect))
+}
+
+#[cfg(all(test, feature = "jwt"))]
+mod tests {
+    use super::*;
+    use crate::auth::service::client_credentials::InMemoryClientStore;
+    use crate::auth::service::hash_service::HashService;
+    use crate::auth::service::jwt_key::JwtKey;
+    use crate::auth::service::jwt_service::{JwtService, get_current_time};
+    use crate::auth::service::token_claims::JwtDataContainer;
+    use crate::auth::test_keys::HMAC_TEST_SECRET;
+    use actix_web::http::{StatusCode, header};
+    use actix_web::{App, test};
+    use jsonwebtoken::Algorithm;
+    use serde_json::{Value, json};
+    use std::sync::Arc;
+
+    fn services() -> (
+        web::Data<JwtService>,
+        web::Data<ClientAuthenticator>,
+        web::Data<TokenExchangeService>,
+    ) {
+        let jwt_service = web::Data::new(JwtService::with_key(
+            JwtKey::hmac(Algorithm::HS256, HMAC_TEST_SECRET).unwrap(),
+        ));
+        let hash_service = HashService::new();
+        let hash = hash_service.hash_password("edge secret").unwrap();
+        let authenticator = ClientAuthenticator::new(
+            Arc::new(InMemoryClientStore::new().with_client("edge", &hash)),
+            hash_service,
+        );
+        let exchange = TokenExchangeService::new().allow_audience("edge", "billing");
+
+        (
+            jwt_service,
+            web::Data::new(authenticator),
+            web::Data::new(exchange),
+        )
+    }
+
+    /// A user token issued through `edge`, so that it can exchange it.
+    fn user_token(jwt_service: &JwtService, data: Value) -> String {
+        jwt_service
+            .generate_token_with_data_container(
+                JwtDataContainer::new(data, get_current_time() + 900).with_audience("edge"),
+            )
+            .unwrap()
+    }
+
+    fn form<'a>(subject_token: &'a str, grant_type: &'a str) -> Vec<(&'a str, &'a str)> {
+        vec![
+            ("grant_type", grant_type),
+            ("subject_token", subject_token),
+            ("subject_token_type", ACCESS_TOKEN_TYPE),
+            ("audience", "billing"),
+            ("scope", "read"),
+            ("client_id", "edge"),
+            ("client_secret", "edge secret"),
+        ]
+    }
+
+    #[actix_web::test]
+    async fn test_token_exchange() {
+        let (jwt_service, authenticator, exchange) = services();
+        let app = test::init_service(
+            App::new()
+                .app_data(jwt_service.clone())
+                .app_data(authenticator)
+                .app_data(exchange)
+                .service(oauth2_scope()),
+        )
+        .await;
+        let token = user_token(&jwt_service, json!({"scope": "read write"}));
+
+        let response = test::call_service(
+            &app,
+            test::TestRequest::post()
+                .uri("/oauth2/token")
+                .set_form(form(&token, TOKEN_EXCHANGE_GRANT_TYPE))
+                .to_request(),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers().get(header::CACHE_CONTROL).unwrap(),
+            "no-store"
+        );
+
+        let body: TokenExchangeResponse = test::read_body_json(response).await;
+        assert_eq!(body.issued_token_type, ACCESS_TOKEN_TYPE);
+        assert_eq!(body.token_type, "Bearer");
+        assert_eq!(body.scope.as_deref(), Some("read"));
+        assert!(body.expires_in <= 300);
+
+        let claims = jwt_service
+            .verify_token::<Value>(&body.access_token)
+            .unwrap();
+        assert_eq!(claims.audience(), ["billing"]);
+        assert_eq!(claims.actor().unwrap().sub, "edge");
+    }
+
+    #[actix_web::test]
+    async fn test_token_exchange_errors() {
+        let (jwt_service, authenticator, exchange) = services();
+        let app = test::init_service(
+            App::new()
+                .app_data(jwt_service.clone())
+                .app_data(authenticator)
+                .app_data(exchange)
+                .service(oauth2_scope()),
+        )
+        .await;
+        let token = user_token(&jwt_service, json!({"scope": "write"}));
+
+        let cases = [
+            (
+                form(&token, "client_credentials"),
+                "auth.unsupported_grant_type",
+            ),
+            (
+                form(&token, TOKEN_EXCHANGE_GRANT_TYPE),
+                "auth.invalid_scope",
+            ),
+            (
+                form("not a token", TOKEN_EXCHANGE_GRANT_TYPE),
+                "auth.invalid_grant",
+            ),
+        ];
+
+        for (form, key) in cases {
+            let response = test::call_service(
+                &app,
+                test::TestRequest::post()
+                    .uri("/oauth2/token")
+                    .set_form(form)
+                    .to_request(),
+            )
+            .await;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+            let body: Value = test::read_body_json(response).await;
+            assert_eq!(body["key"], key);
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_token_exchange_requires_client_credentials() {
+        let (jwt_service, authenticator, exchange) = services();
+        let app = test::init_service(
+            App::new()
+                .app_data(jwt_service.clone())
+                .app_data(authenticator)
+                .app_data(exchange)
+                .service(oauth2_scope()),
+        )
+        .await;
+        let token = user_token(&jwt_service, json!({"scope": "read"}));
+        let mut form = form(&token, TOKEN_EXCHANGE_GRANT_TYPE);
+        form.retain(|(name, _)| *name != "client_secret");
+
+        let response = test::call_service(
+            &app,
+            test::TestRequest::post()
+                .uri("/oauth2/token")
+                .set_form(form)
+                .to_request(),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+}
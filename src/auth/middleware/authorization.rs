@@ -0,0 +1,460 @@
+use crate::auth::error::AuthError;
+use crate::auth::extractors::authenticated::{VerifiedClaims, bearer_token};
+use crate::auth::service::token_codec::{app_token_codec, from_value};
+use actix_web::body::EitherBody;
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse, Transform, forward_ready};
+use actix_web::{Error, HttpMessage};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::future::{Future, Ready, ready};
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::rc::Rc;
+use utoipa::Modify;
+use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityRequirement, SecurityScheme};
+
+/// The name of the security scheme registered by [`BearerSecurity`].
+pub const BEARER_AUTH: &str = "bearer_auth";
+
+/// Exposes the roles and scopes carried by a claim type.
+///
+/// Both default to none, so a type only implements what it has. Scopes sent as a
+/// single space-separated `scope` claim, as OAuth issuers do, can be split here.
+///
+/// # Example
+/// ```
+/// use lunna_actix_utils::auth::middleware::authorization::Permissions;
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct Claims {
+///     roles: Vec<String>,
+///     scope: String,
+/// }
+///
+/// impl Permissions for Claims {
+///     fn roles(&self) -> Vec<&str> {
+///         self.roles.iter().map(String::as_str).collect()
+///     }
+///
+///     fn scopes(&self) -> Vec<&str> {
+///         self.scope.split_whitespace().collect()
+///     }
+/// }
+/// ```
+pub trait Permissions {
+    fn roles(&self) -> Vec<&str> {
+        Vec::new()
+    }
+
+    fn scopes(&self) -> Vec<&str> {
+        Vec::new()
+    }
+}
+
+/// A middleware that only lets requests through when their `Bearer` token is valid and
+/// its claims have every required role and scope.
+///
+/// Tokens are verified like the [`Authenticated`](crate::auth::extractors::authenticated::Authenticated)
+/// extractor does, with the token codec of the app. A missing or invalid
+/// token is answered with `401 Unauthorized`, missing permissions with
+/// `403 Forbidden` and an [`AuthError::Forbidden`] body. Wrap a `Scope` to protect all
+/// its routes, or a single resource.
+///
+/// The verified claims are kept with the request, so an `Authenticated` extractor in
+/// a protected handler doesn't verify the token again.
+///
+/// # Example
+/// ```
+/// use actix_web::{HttpResponse, web};
+/// use lunna_actix_utils::auth::middleware::authorization::{Permissions, RequireAuthorization};
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct Claims {
+///     roles: Vec<String>,
+/// }
+///
+/// impl Permissions for Claims {
+///     fn roles(&self) -> Vec<&str> {
+///         self.roles.iter().map(String::as_str).collect()
+///     }
+/// }
+///
+/// let admin = web::scope("/admin")
+///     .wrap(RequireAuthorization::<Claims>::new().with_role("admin"))
+///     .route("/users", web::get().to(HttpResponse::Ok));
+/// ```
+pub struct RequireAuthorization<T> {
+    requirements: Rc<Requirements>,
+    _claims: PhantomData<fn() -> T>,
+}
+
+#[derive(Clone, Default)]
+struct Requirements {
+    roles: Vec<String>,
+    scopes: Vec<String>,
+}
+
+impl<T> RequireAuthorization<T>
+where
+    T: Permissions + Serialize + DeserializeOwned + Send + Sync + 'static,
+{
+    /// Requires a valid token, with no particular permission.
+    pub fn new() -> Self {
+        RequireAuthorization {
+            requirements: Rc::new(Requirements::default()),
+            _claims: PhantomData,
+        }
+    }
+
+    /// Also requires a role.
+    pub fn with_role(mut self, role: &str) -> Self {
+        Rc::make_mut(&mut self.requirements)
+            .roles
+            .push(role.to_string());
+        self
+    }
+
+    /// Also requires a scope.
+    pub fn with_scope(mut self, scope: &str) -> Self {
+        Rc::make_mut(&mut self.requirements)
+            .scopes
+            .push(scope.to_string());
+        self
+    }
+
+    /// The OpenAPI security requirement matching this middleware, for documenting
+    /// routes added with the `utoipa` builders.
+    pub fn security_requirement(&self) -> SecurityRequirement {
+        SecurityRequirement::new(BEARER_AUTH, self.requirements.scopes.iter())
+    }
+}
+
+impl<T> Default for RequireAuthorization<T>
+where
+    T: Permissions + Serialize + DeserializeOwned + Send + Sync + 'static,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Requirements {
+    fn check(&self, claims: &impl Permissions) -> Result<(), AuthError> {
+        let roles = claims.roles();
+        let scopes = claims.scopes();
+
+        let allowed = self.roles.iter().all(|role| roles.contains(&role.as_str()))
+            && self
+                .scopes
+                .iter()
+                .all(|scope| scopes.contains(&scope.as_str()));
+
+        if !allowed {
+            return Err(AuthError::Forbidden);
+        }
+
+        Ok(())
+    }
+}
+
+impl<S, B, T> Transform<S, ServiceRequest> for RequireAuthorization<T>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+    T: Permissions + Serialize + DeserializeOwned + Send + Sync + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = RequireAuthorizationMiddleware<S, T>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequireAuthorizationMiddleware {
+            service: Rc::new(service),
+            requirements: self.requirements.clone(),
+            _claims: PhantomData,
+        }))
+    }
+}
+
+/// The service created by [`RequireAuthorization`].
+pub struct RequireAuthorizationMiddleware<S, T> {
+    service: Rc<S>,
+    requirements: Rc<Requirements>,
+    _claims: PhantomData<fn() -> T>,
+}
+
+impl<S, B, T> Service<ServiceRequest> for RequireAuthorizationMiddleware<S, T>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+    T: Permissions + Serialize + DeserializeOwned + Send + Sync + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let requirements = self.requirements.clone();
+
+        Box::pin(async move {
+            if let Err(error) = authorize::<T>(&req, &requirements).await {
+                return Ok(req.error_response(error).map_into_right_body());
+            }
+
+            service
+                .call(req)
+                .await
+                .map(ServiceResponse::map_into_left_body)
+        })
+    }
+}
+
+async fn authorize<T>(req: &ServiceRequest, requirements: &Requirements) -> Result<(), AuthError>
+where
+    T: Permissions + Serialize + DeserializeOwned + Send + Sync + 'static,
+{
+    let codec = app_token_codec(req.request()).ok_or(AuthError::InternalError)?;
+    let token = bearer_token(req.request()).ok_or(AuthError::MissingToken)?;
+
+    let claims = codec.decode_async(token).await?;
+    requirements.check(&from_value::<T>(claims.data.clone())?)?;
+
+    req.extensions_mut().insert(VerifiedClaims(claims));
+    Ok(())
+}
+
+/// Registers the [`BEARER_AUTH`] JWT security scheme in an OpenAPI document.
+///
+/// # Example
+/// ```
+/// use lunna_actix_utils::auth::middleware::authorization::BearerSecurity;
+/// use utoipa::OpenApi;
+///
+/// #[utoipa::path(get, path = "/orders", security(("bearer_auth" = ["orders:read"])))]
+/// async fn orders() {}
+///
+/// #[derive(OpenApi)]
+/// #[openapi(paths(orders), modifiers(&BearerSecurity))]
+/// struct ApiDoc;
+///
+/// let openapi = ApiDoc::openapi();
+/// assert!(openapi.components.unwrap().security_schemes.contains_key("bearer_auth"));
+/// ```
+pub struct BearerSecurity;
+
+impl Modify for BearerSecurity {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        openapi
+            .components
+            .get_or_insert_with(Default::default)
+            .add_security_scheme(
+                BEARER_AUTH,
+                SecurityScheme::Http(
+                    HttpBuilder::new()
+                        .scheme(HttpAuthScheme::Bearer)
+                        .bearer_format("JWT")
+                        .build(),
+                ),
+            );
+    }
+}
+
+#[cfg(all(test, feature = "jwt"))]
+mod tests {
+    use super::*;
+    use crate::auth::extractors::authenticated::Authenticated;
+    use crate::auth::service::clock::{Clock, MockClock};
+    use crate::auth::service::jwt_key::JwtKey;
+    use crate::auth::service::jwt_service::JwtService;
+    use crate::auth::service::token_claims::{JwtDataContainer, get_current_time};
+    use crate::auth::service::token_codec::TokenCodec;
+    use crate::auth::test_keys::*;
+    use actix_web::http::StatusCode;
+    use actix_web::http::header::{AUTHORIZATION, WWW_AUTHENTICATE};
+    use actix_web::{App, HttpResponse, test, web};
+    use async_trait::async_trait;
+    use jsonwebtoken::Algorithm;
+    use serde::Deserialize;
+    use serde_json::Value;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use utoipa::OpenApi;
+
+    #[derive(Serialize, Deserialize)]
+    struct Claims {
+        roles: Vec<String>,
+        scope: String,
+    }
+
+    impl Permissions for Claims {
+        fn roles(&self) -> Vec<&str> {
+            self.roles.iter().map(String::as_str).collect()
+        }
+
+        fn scopes(&self) -> Vec<&str> {
+            self.scope.split_whitespace().collect()
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_require_authorization() {
+        let clock = Arc::new(MockClock::new(1_700_000_000));
+        let jwt_service = web::Data::new(
+            JwtService::with_key(JwtKey::hmac(Algorithm::HS256, HMAC_TEST_SECRET).unwrap())
+                .with_clock(clock.clone()),
+        );
+        let token = |roles: &[&str], scope: &str| {
+            let claims = Claims {
+                roles: roles.iter().map(|role| role.to_string()).collect(),
+                scope: scope.to_string(),
+            };
+            jwt_service
+                .generate_token(claims, clock.now() + 60)
+                .unwrap()
+        };
+        let admin = token(&["admin"], "orders:read orders:write");
+        let reader = token(&["user"], "orders:read");
+
+        let app = test::init_service(
+            App::new()
+                .app_data(jwt_service.clone())
+                .service(
+                    web::scope("/admin")
+                        .wrap(RequireAuthorization::<Claims>::new().with_role("admin"))
+                        .route("/users", web::get().to(HttpResponse::Ok)),
+                )
+                .service(
+                    web::resource("/orders")
+                        .wrap(RequireAuthorization::<Claims>::new().with_scope("orders:read"))
+                        .route(web::get().to(HttpResponse::Ok)),
+                ),
+        )
+        .await;
+
+        let request = |uri: &str, token: Option<&str>| {
+            let mut request = test::TestRequest::get().uri(uri);
+            if let Some(token) = token {
+                request = request.insert_header((AUTHORIZATION, format!("Bearer {token}")));
+            }
+            request.to_request()
+        };
+
+        let response = test::call_service(&app, request("/admin/users", Some(&admin))).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = test::call_service(&app, request("/orders", Some(&reader))).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = test::call_service(&app, request("/admin/users", None)).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = test::call_service(&app, request("/admin/users", Some(&reader))).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        assert_eq!(
+            response.headers().get(WWW_AUTHENTICATE).unwrap(),
+            r#"Bearer error="insufficient_scope""#
+        );
+        let body: serde_json::Value = test::read_body_json(response).await;
+        assert_eq!(body["key"], "auth.forbidden");
+    }
+
+    /// A codec counting how many tokens it verifies.
+    struct CountingCodec {
+        inner: JwtService,
+        decodes: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl TokenCodec for CountingCodec {
+        fn encode(&self, claims: JwtDataContainer<Value>) -> Result<String, AuthError> {
+            TokenCodec::encode(&self.inner, claims)
+        }
+
+        fn decode(&self, token: &str) -> Result<JwtDataContainer<Value>, AuthError> {
+            self.decodes.fetch_add(1, Ordering::Relaxed);
+            TokenCodec::decode(&self.inner, token)
+        }
+
+        async fn decode_async(&self, token: &str) -> Result<JwtDataContainer<Value>, AuthError> {
+            self.decodes.fetch_add(1, Ordering::Relaxed);
+            TokenCodec::decode_async(&self.inner, token).await
+        }
+
+        async fn revoke(&self, token: &str) -> Result<(), AuthError> {
+            TokenCodec::revoke(&self.inner, token).await
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_authenticated_reuses_verified_claims() {
+        async fn orders(user: Authenticated<Claims>) -> HttpResponse {
+            HttpResponse::Ok().body(user.into_inner().data.scope)
+        }
+
+        let codec = Arc::new(CountingCodec {
+            inner: JwtService::with_key(JwtKey::hmac(Algorithm::HS256, HMAC_TEST_SECRET).unwrap()),
+            decodes: AtomicUsize::new(0),
+        });
+        let claims = Claims {
+            roles: Vec::new(),
+            scope: "orders:read".to_string(),
+        };
+        let token = (codec.clone() as Arc<dyn TokenCodec>)
+            .generate_token(claims, get_current_time() + 60)
+            .unwrap();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::from(codec.clone() as Arc<dyn TokenCodec>))
+                .service(
+                    web::resource("/orders")
+                        .wrap(RequireAuthorization::<Claims>::new().with_scope("orders:read"))
+                        .route(web::get().to(orders)),
+                ),
+        )
+        .await;
+
+        let response = test::call_service(
+            &app,
+            test::TestRequest::get()
+                .uri("/orders")
+                .insert_header((AUTHORIZATION, format!("Bearer {token}")))
+                .to_request(),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(test::read_body(response).await, "orders:read");
+        assert_eq!(codec.decodes.load(Ordering::Relaxed), 1);
+    }
+
+    #[actix_web::test]
+    async fn test_bearer_security() {
+        #[derive(OpenApi)]
+        #[openapi(modifiers(&BearerSecurity))]
+        struct ApiDoc;
+
+        let openapi = ApiDoc::openapi();
+        assert!(
+            openapi
+                .components
+                .unwrap()
+                .security_schemes
+                .contains_key(BEARER_AUTH)
+        );
+
+        let requirement = RequireAuthorization::<Claims>::new()
+            .with_scope("orders:read")
+            .security_requirement();
+        assert_eq!(
+            serde_json::to_value(requirement).unwrap(),
+            serde_json::json!({ BEARER_AUTH: ["orders:read"] })
+        );
+    }
+}
@@ -1,7 +1,14 @@
 pub mod error;
+pub mod extractors;
+pub mod handler;
+pub mod middleware;
+pub mod policy;
 pub mod service;
 pub mod request;
 pub mod response;
 
+#[cfg(test)]
+pub(crate) mod test_keys;
+
 #[cfg(any(feature = "sql", doc))]
 pub mod sql;
\ No newline at end of file
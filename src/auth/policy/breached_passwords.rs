@@ -0,0 +1,54 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// A source of known-compromised passwords used by
+/// [`PasswordPolicy`](super::password_policy::PasswordPolicy).
+pub trait BreachedPasswords: Send + Sync {
+    /// Returns `true` if the password appears in a known breach.
+    fn is_breached(&self, password: &str) -> bool;
+}
+
+/// A plain list of breached passwords loaded from a local file, one per line.
+///
+/// Lookups are exact and case-sensitive. Empty lines are ignored.
+#[derive(Debug, Clone, Default)]
+pub struct BreachedPasswordList {
+    passwords: HashSet<String>,
+}
+
+impl BreachedPasswordList {
+    pub fn from_file(path: impl AsRef<Path>) -> io::Result<Self> {
+        let contents = fs::read_to_string(path)?;
+
+        Ok(contents.lines().collect())
+    }
+
+    pub fn len(&self) -> usize {
+        self.passwords.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.passwords.is_empty()
+    }
+}
+
+impl<'a> FromIterator<&'a str> for BreachedPasswordList {
+    fn from_iter<I: IntoIterator<Item = &'a str>>(iter: I) -> Self {
+        BreachedPasswordList {
+            passwords: iter
+                .into_iter()
+                .map(|line| line.trim_end_matches('\r'))
+                .filter(|line| !line.is_empty())
+                .map(str::to_string)
+                .collect(),
+        }
+    }
+}
+
+impl BreachedPasswords for BreachedPasswordList {
+    fn is_breached(&self, password: &str) -> bool {
+        self.passwords.contains(password)
+    }
+}
@@ -0,0 +1,5 @@
+pub mod breached_passwords;
+pub mod password_policy;
+
+#[cfg(feature = "hibp")]
+pub mod pwned_passwords;
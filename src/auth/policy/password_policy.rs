@@ -0,0 +1,509 @@
+use crate::auth::error::AuthError;
+use crate::auth::policy::breached_passwords::BreachedPasswords;
+use crate::auth::request::register_request::RegisterRequestLike;
+use crate::util::text_util::TextUtil;
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Serialize, Serializer};
+use std::borrow::Cow;
+use std::fmt;
+use std::sync::Arc;
+use strum::{AsRefStr, EnumString};
+use thiserror::Error;
+use validator::{ValidationError, ValidationErrors};
+
+/// A single rule broken by a password.
+///
+/// Like [`AuthError`], every violation has an i18n key
+/// under the `auth` prefix, e.g. `auth.password_too_short`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error, AsRefStr, EnumString)]
+pub enum PasswordPolicyViolation {
+    #[error("The password is too short")]
+    PasswordTooShort,
+    #[error("The password is too long")]
+    PasswordTooLong,
+    #[error("The password must contain a lowercase letter")]
+    PasswordMissingLowercase,
+    #[error("The password must contain an uppercase letter")]
+    PasswordMissingUppercase,
+    #[error("The password must contain a digit")]
+    PasswordMissingDigit,
+    #[error("The password must contain a symbol")]
+    PasswordMissingSymbol,
+    #[error("The password is too easy to guess")]
+    PasswordTooWeak,
+    #[error("The password is too similar to the username or email")]
+    PasswordTooSimilar,
+    #[error("The password has appeared in a data breach")]
+    PasswordBreached,
+}
+
+impl PasswordPolicyViolation {
+    pub fn i18n_key(&self) -> String {
+        TextUtil::i18n_key_with_prefix("auth", self)
+    }
+}
+
+impl Serialize for PasswordPolicyViolation {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut map = serializer.serialize_map(Some(2))?;
+        map.serialize_entry("error", &self.to_string())?;
+        map.serialize_entry("key", &self.i18n_key())?;
+        map.end()
+    }
+}
+
+/// Collapses a violation into an [`AuthError`] for flows that only report one error,
+/// such as a password change.
+impl From<PasswordPolicyViolation> for AuthError {
+    fn from(violation: PasswordPolicyViolation) -> Self {
+        match violation {
+            PasswordPolicyViolation::PasswordBreached => AuthError::BreachedPassword,
+            _ => AuthError::InvalidPassword,
+        }
+    }
+}
+
+impl From<PasswordPolicyViolation> for ValidationError {
+    fn from(violation: PasswordPolicyViolation) -> Self {
+        let mut error = ValidationError::new("password_policy");
+        error.code = Cow::Owned(violation.i18n_key());
+        error.with_message(Cow::Owned(violation.to_string()))
+    }
+}
+
+/// Configurable rules for new passwords.
+///
+/// The defaults match the bounds of [`LoginRequest`](crate::auth::request::login_request::LoginRequest)
+/// and only add the username/email similarity check, so deployments opt in to the
+/// stricter rules.
+///
+/// # Example
+/// ```
+/// use lunna_actix_utils::auth::policy::password_policy::{PasswordPolicy, PasswordPolicyViolation};
+///
+/// let mut policy = PasswordPolicy::default();
+/// policy.require_digit = true;
+///
+/// assert_eq!(
+///     policy.check("correcthorse", "lunna", "hi@lunna.dev"),
+///     Err(vec![PasswordPolicyViolation::PasswordMissingDigit])
+/// );
+/// ```
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PasswordPolicy {
+    /// Minimum number of characters.
+    pub min_length: usize,
+
+    /// Maximum number of characters.
+    pub max_length: usize,
+
+    pub require_lowercase: bool,
+    pub require_uppercase: bool,
+    pub require_digit: bool,
+
+    /// Requires a character that is neither alphanumeric nor whitespace.
+    pub require_symbol: bool,
+
+    /// Minimum estimated entropy in bits, see [`PasswordPolicy::estimate_entropy`].
+    /// `0.0` disables the check.
+    pub min_entropy_bits: f64,
+
+    /// Rejects passwords that contain the username or the local part of the email,
+    /// or are contained in them.
+    pub reject_similar_to_identity: bool,
+
+    #[serde(skip)]
+    breached_passwords: Option<Arc<dyn BreachedPasswords>>,
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        PasswordPolicy {
+            min_length: 8,
+            max_length: 128,
+            require_lowercase: false,
+            require_uppercase: false,
+            require_digit: false,
+            require_symbol: false,
+            min_entropy_bits: 0.0,
+            reject_similar_to_identity: true,
+            breached_passwords: None,
+        }
+    }
+}
+
+impl fmt::Debug for PasswordPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PasswordPolicy")
+            .field("min_length", &self.min_length)
+            .field("max_length", &self.max_length)
+            .field("require_lowercase", &self.require_lowercase)
+            .field("require_uppercase", &self.require_uppercase)
+            .field("require_digit", &self.require_digit)
+            .field("require_symbol", &self.require_symbol)
+            .field("min_entropy_bits", &self.min_entropy_bits)
+            .field(
+                "reject_similar_to_identity",
+                &self.reject_similar_to_identity,
+            )
+            .field("breached_passwords", &self.breached_passwords.is_some())
+            .finish()
+    }
+}
+
+impl PasswordPolicy {
+    /// Rejects any password found in the given source.
+    pub fn with_breached_passwords(mut self, source: impl BreachedPasswords + 'static) -> Self {
+        self.breached_passwords = Some(Arc::new(source));
+        self
+    }
+
+    /// Checks a password against every rule, returning all the violations found.
+    pub fn check(
+        &self,
+        password: &str,
+        username: &str,
+        email: &str,
+    ) -> Result<(), Vec<PasswordPolicyViolation>> {
+        let mut violations = Vec::new();
+        let length = password.chars().count();
+
+        if length < self.min_length {
+            violations.push(PasswordPolicyViolation::PasswordTooShort);
+        }
+
+        if length > self.max_length {
+            violations.push(PasswordPolicyViolation::PasswordTooLong);
+        }
+
+        let classes = CharClasses::of(password);
+
+        if self.require_lowercase && !classes.lowercase {
+            violations.push(PasswordPolicyViolation::PasswordMissingLowercase);
+        }
+
+        if self.require_uppercase && !classes.uppercase {
+            violations.push(PasswordPolicyViolation::PasswordMissingUppercase);
+        }
+
+        if self.require_digit && !classes.digit {
+            violations.push(PasswordPolicyViolation::PasswordMissingDigit);
+        }
+
+        if self.require_symbol && !classes.symbol {
+            violations.push(PasswordPolicyViolation::PasswordMissingSymbol);
+        }
+
+        if Self::estimate_entropy(password) < self.min_entropy_bits {
+            violations.push(PasswordPolicyViolation::PasswordTooWeak);
+        }
+
+        if self.reject_similar_to_identity && is_similar_to_identity(password, username, email) {
+            violations.push(PasswordPolicyViolation::PasswordTooSimilar);
+        }
+
+        if let Some(breached_passwords) = &self.breached_passwords
+            && breached_passwords.is_breached(password)
+        {
+            violations.push(PasswordPolicyViolation::PasswordBreached);
+        }
+
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(violations)
+        }
+    }
+
+    /// Checks the password of a registration request.
+    ///
+    /// The violations are reported as `validator` errors on the `password` field, with
+    /// the i18n key as the error code, so they can be returned the same way as the
+    /// errors from [`ValidatedJson`](crate::extractors::validated_json::ValidatedJson).
+    pub fn validate_register(
+        &self,
+        register_request: &dyn RegisterRequestLike,
+    ) -> Result<(), ValidationErrors> {
+        let violations = match self.check(
+            register_request.password(),
+            register_request.username(),
+            register_request.email(),
+        ) {
+            Ok(()) => return Ok(()),
+            Err(violations) => violations,
+        };
+
+        let mut errors = ValidationErrors::new();
+        for violation in violations {
+            errors.add("password", violation.into());
+        }
+
+        Err(errors)
+    }
+
+    /// Estimates the entropy of a password in bits.
+    ///
+    /// This is the naive `length * log2(pool)` estimate, where the pool is the sum of
+    /// the character classes in use. Repeated characters only count once towards the
+    /// length, so `aaaaaaaa` scores like `a`.
+    pub fn estimate_entropy(password: &str) -> f64 {
+        let classes = CharClasses::of(password);
+        let pool = [
+            (classes.lowercase, 26),
+            (classes.uppercase, 26),
+            (classes.digit, 10),
+            (classes.symbol, 33),
+            (classes.other, 100),
+        ]
+        .iter()
+        .filter(|(present, _)| *present)
+        .map(|(_, size)| size)
+        .sum::<u32>();
+
+        if pool == 0 {
+            return 0.0;
+        }
+
+        let mut unique: Vec<char> = password.chars().collect();
+        unique.sort_unstable();
+        unique.dedup();
+
+        unique.len() as f64 * f64::from(pool).log2()
+    }
+}
+
+#[derive(Default)]
+struct CharClasses {
+    lowercase: bool,
+    uppercase: bool,
+    digit: bool,
+    symbol: bool,
+    other: bool,
+}
+
+impl CharClasses {
+    fn of(password: &str) -> Self {
+        let mut classes = CharClasses::default();
+
+        for c in password.chars() {
+            match c {
+                'a'..='z' => classes.lowercase = true,
+                'A'..='Z' => classes.uppercase = true,
+                '0'..='9' => classes.digit = true,
+                c if c.is_ascii_punctuation() => classes.symbol = true,
+                c if c.is_whitespace() => {}
+                c if c.is_lowercase() => classes.lowercase = true,
+                c if c.is_uppercase() => classes.uppercase = true,
+                c if c.is_alphanumeric() => classes.other = true,
+                _ => classes.symbol = true,
+            }
+        }
+
+        classes
+    }
+}
+
+/// Identity parts shorter than this are too common to compare against.
+const MIN_IDENTITY_PART_LEN: usize = 3;
+
+fn is_similar_to_identity(password: &str, username: &str, email: &str) -> bool {
+    let password = password.to_lowercase();
+    let email_local_part = email.split('@').next().unwrap_or_default();
+
+    [username, email_local_part]
+        .iter()
+        .map(|part| part.to_lowercase())
+        .filter(|part| part.chars().count() >= MIN_IDENTITY_PART_LEN)
+        .any(|part| password.contains(&part) || part.contains(&password))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::policy::breached_passwords::BreachedPasswordList;
+    use crate::auth::request::register_request::RegisterRequest;
+
+    const USERNAME: &str = "new_user";
+    const EMAIL: &str = "someone@example.com";
+
+    #[test]
+    fn test_default_policy_accepts_reasonable_password() {
+        let policy = PasswordPolicy::default();
+        assert_eq!(policy.check("securePass123", USERNAME, EMAIL), Ok(()));
+    }
+
+    #[test]
+    fn test_length_bounds() {
+        let policy = PasswordPolicy::default();
+
+        assert_eq!(
+            policy.check("short", USERNAME, EMAIL),
+            Err(vec![PasswordPolicyViolation::PasswordTooShort])
+        );
+        assert_eq!(
+            policy.check(&"x".repeat(129), USERNAME, EMAIL),
+            Err(vec![PasswordPolicyViolation::PasswordTooLong])
+        );
+    }
+
+    #[test]
+    fn test_character_classes() {
+        let policy = PasswordPolicy {
+            require_lowercase: true,
+            require_uppercase: true,
+            require_digit: true,
+            require_symbol: true,
+            ..PasswordPolicy::default()
+        };
+
+        assert_eq!(
+            policy.check("ALLUPPERCASE", USERNAME, EMAIL),
+            Err(vec![
+                PasswordPolicyViolation::PasswordMissingLowercase,
+                PasswordPolicyViolation::PasswordMissingDigit,
+                PasswordPolicyViolation::PasswordMissingSymbol,
+            ])
+        );
+        assert_eq!(policy.check("Secure-Pass123", USERNAME, EMAIL), Ok(()));
+    }
+
+    #[test]
+    fn test_entropy() {
+        let policy = PasswordPolicy {
+            min_entropy_bits: 50.0,
+            ..PasswordPolicy::default()
+        };
+
+        assert_eq!(
+            policy.check("aaaaaaaaaaaa", USERNAME, EMAIL),
+            Err(vec![PasswordPolicyViolation::PasswordTooWeak])
+        );
+        assert_eq!(policy.check("k7#Lq9!zWm2@", USERNAME, EMAIL), Ok(()));
+        assert_eq!(PasswordPolicy::estimate_entropy(""), 0.0);
+    }
+
+    #[test]
+    fn test_similarity() {
+        let policy = PasswordPolicy::default();
+
+        assert_eq!(
+            policy.check("my_new_user_pass", USERNAME, EMAIL),
+            Err(vec![PasswordPolicyViolation::PasswordTooSimilar])
+        );
+        assert_eq!(
+            policy.check("SOMEONE-2024", USERNAME, EMAIL),
+            Err(vec![PasswordPolicyViolation::PasswordTooSimilar])
+        );
+    }
+
+    #[test]
+    fn test_breached_passwords() {
+        let policy = PasswordPolicy::default()
+            .with_breached_passwords(BreachedPasswordList::from_iter(["password1234"]));
+
+        assert_eq!(
+            policy.check("password1234", USERNAME, EMAIL),
+            Err(vec![PasswordPolicyViolation::PasswordBreached])
+        );
+    }
+
+    #[test]
+    fn test_breached_passwords_from_file() {
+        let path = std::env::temp_dir().join("lunna_actix_utils_breached_passwords.txt");
+        std::fs::write(&path, "password1234\r\n\nqwertyuiop\n").unwrap();
+
+        let list = BreachedPasswordList::from_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(list.len(), 2);
+        assert!(list.is_breached("qwertyuiop"));
+        assert!(!list.is_breached("securePass123"));
+    }
+
+    #[test]
+    fn test_i18n_keys() {
+        assert_eq!(
+            PasswordPolicyViolation::PasswordTooShort.i18n_key(),
+            "auth.password_too_short"
+        );
+        assert_eq!(
+            serde_json::to_value(PasswordPolicyViolation::PasswordBreached).unwrap(),
+            serde_json::json!({
+                "error": "The password has appeared in a data breach",
+                "key": "auth.password_breached",
+            })
+        );
+    }
+
+    #[test]
+    fn test_into_auth_error() {
+        assert!(matches!(
+            AuthError::from(PasswordPolicyViolation::PasswordBreached),
+            AuthError::BreachedPassword
+        ));
+        assert!(matches!(
+            AuthError::from(PasswordPolicyViolation::PasswordTooShort),
+            AuthError::InvalidPassword
+        ));
+    }
+
+    #[test]
+    fn test_validate_register() {
+        let policy = PasswordPolicy::default();
+        let request = RegisterRequest {
+            username: USERNAME.to_string(),
+            email: EMAIL.to_string(),
+            password: "short".to_string(),
+        };
+
+        let errors = policy.validate_register(&request).unwrap_err();
+        let field_errors = errors.field_errors();
+
+        assert_eq!(field_errors["password"][0].code, "auth.password_too_short");
+    }
+
+    #[test]
+    fn test_register_request_uses_policy_bounds() {
+        use validator::Validate;
+
+        let request = |password: String| RegisterRequest {
+            username: USERNAME.to_string(),
+            email: EMAIL.to_string(),
+            password,
+        };
+        let policy = PasswordPolicy {
+            min_length: 12,
+            max_length: 256,
+            ..PasswordPolicy::default()
+        };
+
+        // Long enough for the old fixed 8 character bound, not for the policy.
+        let short = request("correcthors".to_string());
+        assert!(short.validate().is_ok());
+        let errors = short.validate_with_policy(&policy).unwrap_err();
+        assert_eq!(
+            errors.field_errors()["password"][0].code,
+            "auth.password_too_short"
+        );
+
+        // Over the old fixed 128 character bound, within the policy.
+        assert!(
+            request("x".repeat(200))
+                .validate_with_policy(&policy)
+                .is_ok()
+        );
+
+        let errors = RegisterRequest {
+            username: "me".to_string(),
+            ..short
+        }
+        .validate_with_policy(&policy)
+        .unwrap_err();
+        assert!(errors.field_errors().contains_key("username"));
+        assert!(errors.field_errors().contains_key("password"));
+    }
+}
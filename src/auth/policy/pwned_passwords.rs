@@ -0,0 +1,300 @@
+use crate::auth::error::AuthError;
+use crate::auth::policy::breached_passwords::BreachedPasswords;
+use memmap2::Mmap;
+use sha1::{Digest, Sha1};
+use std::cmp::Ordering;
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const HASH_LEN: usize = 40;
+
+/// The length of the hash prefix a range is named after.
+const PREFIX_LEN: usize = 5;
+
+/// An offline copy of the Pwned Passwords corpus.
+///
+/// Two layouts produced by the official downloader are supported:
+/// - a single file, opened with [`PwnedPasswords::open`]: one `SHA1:COUNT` line per
+///   password, with the uppercase hex SHA-1 hashes sorted. It is memory-mapped and
+///   searched in place, so opening it is instant and each lookup only touches a few
+///   pages, even for the full corpus;
+/// - a directory of range files, opened with [`PwnedPasswords::open_ranges`]: one
+///   `<PREFIX>.txt` file per 5-hex-digit hash prefix, holding the sorted
+///   `SUFFIX:COUNT` lines of the range API. Each lookup reads a single range.
+///
+/// Only the hash of a password is ever compared, and nothing leaves the machine.
+///
+/// Requires the `hibp` feature.
+///
+/// # Example
+/// ```no_run
+/// use lunna_actix_utils::auth::policy::password_policy::PasswordPolicy;
+/// use lunna_actix_utils::auth::policy::pwned_passwords::PwnedPasswords;
+///
+/// let pwned = PwnedPasswords::open("pwnedpasswords.txt").unwrap();
+/// let policy = PasswordPolicy::default().with_breached_passwords(pwned);
+/// ```
+pub struct PwnedPasswords {
+    corpus: Corpus,
+    min_count: u64,
+}
+
+enum Corpus {
+    File(Mmap),
+    Ranges(PathBuf),
+}
+
+impl PwnedPasswords {
+    /// Opens a single-file corpus, as downloaded in single-file mode.
+    ///
+    /// The file is memory-mapped, so this fails only if it can't be opened or mapped.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = File::open(path)?;
+
+        // SAFETY: the corpus is a read-only data file. Truncating it while the server
+        // runs is undefined behavior, same as for any other memory-mapped file.
+        let mmap = unsafe { Mmap::map(&file)? };
+
+        Ok(PwnedPasswords {
+            corpus: Corpus::File(mmap),
+            min_count: 1,
+        })
+    }
+
+    /// Opens a directory of range files, as downloaded from the range API.
+    ///
+    /// Files are named after their uppercase prefix, with or without a `.txt`
+    /// extension. A missing range counts as no match, but a range that exists and
+    /// can't be read is an error.
+    pub fn open_ranges(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        if !path.is_dir() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotADirectory,
+                format!("{} is not a directory", path.display()),
+            ));
+        }
+
+        Ok(PwnedPasswords {
+            corpus: Corpus::Ranges(path.to_path_buf()),
+            min_count: 1,
+        })
+    }
+
+    /// Only treats passwords seen at least `min_count` times as breached.
+    pub fn with_min_count(mut self, min_count: u64) -> Self {
+        self.min_count = min_count;
+        self
+    }
+
+    /// Returns how many times the password appears in the corpus, `0` if never.
+    ///
+    /// Fails if the range of the password exists but can't be read.
+    pub fn count(&self, password: &str) -> io::Result<u64> {
+        let digest = Sha1::digest(password.as_bytes());
+        let mut hash = [0u8; HASH_LEN];
+        for (i, byte) in digest.iter().enumerate() {
+            hash[i * 2] = HEX[usize::from(byte >> 4)];
+            hash[i * 2 + 1] = HEX[usize::from(byte & 0x0f)];
+        }
+
+        let count = match &self.corpus {
+            Corpus::File(mmap) => find(mmap, &hash),
+            Corpus::Ranges(directory) => {
+                let (prefix, suffix) = hash.split_at(PREFIX_LEN);
+                read_range(directory, prefix)?.and_then(|range| find(&range, suffix))
+            }
+        };
+
+        Ok(count.unwrap_or(0))
+    }
+
+    /// Fails with [`AuthError::BreachedPassword`] if the password is in the corpus,
+    /// or [`AuthError::InternalError`] if it can't be looked up.
+    pub fn check(&self, password: &str) -> Result<(), AuthError> {
+        let count = self.count(password).map_err(|_| AuthError::InternalError)?;
+        if count >= self.min_count.max(1) {
+            return Err(AuthError::BreachedPassword);
+        }
+
+        Ok(())
+    }
+}
+
+/// A password that can't be looked up counts as breached, so an unreadable corpus
+/// rejects passwords rather than letting every one through.
+impl BreachedPasswords for PwnedPasswords {
+    fn is_breached(&self, password: &str) -> bool {
+        self.count(password)
+            .map_or(true, |count| count >= self.min_count.max(1))
+    }
+}
+
+const HEX: &[u8; 16] = b"0123456789ABCDEF";
+
+/// Reads the range file of `prefix`, `None` if there is none.
+fn read_range(directory: &Path, prefix: &[u8]) -> io::Result<Option<Vec<u8>>> {
+    let prefix = std::str::from_utf8(prefix).map_err(io::Error::other)?;
+
+    for name in [format!("{prefix}.txt"), prefix.to_string()] {
+        match std::fs::read(directory.join(name)) {
+            Ok(range) => return Ok(Some(range)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err),
+        }
+    }
+
+    Ok(None)
+}
+
+/// Binary searches the sorted lines of `data` for `hash`, a full hash or the suffix
+/// of one, and returns its count.
+fn find(data: &[u8], hash: &[u8]) -> Option<u64> {
+    let mut low = 0;
+    let mut high = data.len();
+
+    while low < high {
+        let mid = low + (high - low) / 2;
+        let start = data[..mid]
+            .iter()
+            .rposition(|&b| b == b'\n')
+            .map_or(0, |i| i + 1);
+        let end = data[mid..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .map_or(data.len(), |i| mid + i);
+        let line = &data[start..end];
+        let key = &line[..line.len().min(hash.len())];
+
+        match compare_hash(key, hash) {
+            Ordering::Equal => return Some(parse_count(&line[hash.len()..])),
+            Ordering::Less => low = end + 1,
+            Ordering::Greater => high = start,
+        }
+    }
+
+    None
+}
+
+fn compare_hash(key: &[u8], hash: &[u8]) -> Ordering {
+    key.iter()
+        .map(u8::to_ascii_uppercase)
+        .cmp(hash.iter().copied())
+}
+
+/// Parses the `:COUNT` part of a line. A missing count counts as one occurrence.
+fn parse_count(rest: &[u8]) -> u64 {
+    std::str::from_utf8(rest)
+        .ok()
+        .and_then(|rest| rest.trim().strip_prefix(':'))
+        .and_then(|count| count.parse().ok())
+        .unwrap_or(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // SHA-1 of "password", "123456" and "qwerty", plus some filler, sorted.
+    const CORPUS: &str = "\
+000000005AD76BD555C1D6D771DE417A4B87E4B4:4\r
+5BAA61E4C9B93F3F0682250B6CF8331B7EE68FD8:9545824\r
+7C4A8D09CA3762AF61E59520943DC26494F8941B:37359195\r
+b1b3773a05c0ed0176787a4f1574ff0075f7521e:3
+FFFFFFF8A0382AA9C8D9536EFBA77F261815334D:2
+";
+
+    fn open_corpus(name: &str) -> PwnedPasswords {
+        let path = std::env::temp_dir().join(name);
+        std::fs::write(&path, CORPUS).unwrap();
+
+        let pwned = PwnedPasswords::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        pwned
+    }
+
+    #[test]
+    fn test_count() {
+        let pwned = open_corpus("lunna_actix_utils_pwned_count.txt");
+
+        assert_eq!(pwned.count("password").unwrap(), 9545824);
+        assert_eq!(pwned.count("123456").unwrap(), 37359195);
+        assert_eq!(pwned.count("qwerty").unwrap(), 3);
+        assert_eq!(pwned.count("securePass123").unwrap(), 0);
+    }
+
+    #[test]
+    fn test_check() {
+        let pwned = open_corpus("lunna_actix_utils_pwned_check.txt").with_min_count(10);
+
+        assert!(matches!(
+            pwned.check("password"),
+            Err(AuthError::BreachedPassword)
+        ));
+        assert!(pwned.check("qwerty").is_ok());
+        assert!(pwned.check("securePass123").is_ok());
+    }
+
+    // The ranges of "password" and "123456", as served by the range API.
+    const RANGES: [(&str, &str); 2] = [
+        (
+            "5BAA6.txt",
+            "\
+003D68EB55068C33ACE09247EE4C639306B:3\r
+1E4C9B93F3F0682250B6CF8331B7EE68FD8:9545824\r
+1E4C9B93F3F0682250B6CF8331B7EE68FD9:0\r
+FFF8D6E35CBB5C4B8BDC0E56CD8A4FB13E8:1",
+        ),
+        (
+            "7C4A8",
+            "\
+D09CA3762AF61E59520943DC26494F8941B:37359195
+D09CA3762AF61E59520943DC26494F8941C:2
+",
+        ),
+    ];
+
+    #[test]
+    fn test_ranges() {
+        let directory = std::env::temp_dir().join("lunna_actix_utils_pwned_ranges");
+        std::fs::create_dir_all(&directory).unwrap();
+        for (name, range) in RANGES {
+            std::fs::write(directory.join(name), range).unwrap();
+        }
+
+        let pwned = PwnedPasswords::open_ranges(&directory).unwrap();
+        assert_eq!(pwned.count("password").unwrap(), 9545824);
+        assert_eq!(pwned.count("123456").unwrap(), 37359195);
+        // Its range is missing.
+        assert_eq!(pwned.count("qwerty").unwrap(), 0);
+        assert!(pwned.check("password").is_err());
+
+        std::fs::remove_dir_all(&directory).unwrap();
+        assert!(PwnedPasswords::open_ranges(&directory).is_err());
+    }
+
+    #[test]
+    fn test_unreadable_range_fails_closed() {
+        let directory = std::env::temp_dir().join("lunna_actix_utils_pwned_unreadable");
+        // A directory where the range of "password" should be can't be read.
+        std::fs::create_dir_all(directory.join("5BAA6.txt")).unwrap();
+
+        let pwned = PwnedPasswords::open_ranges(&directory).unwrap();
+        assert!(pwned.count("password").is_err());
+        assert!(pwned.is_breached("password"));
+        assert!(matches!(
+            pwned.check("password"),
+            Err(AuthError::InternalError)
+        ));
+        // Other ranges are simply missing.
+        assert_eq!(pwned.count("123456").unwrap(), 0);
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_empty_corpus() {
+        assert_eq!(find(b"", &[b'0'; HASH_LEN]), None);
+    }
+}
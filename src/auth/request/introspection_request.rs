@@ -0,0 +1,56 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Represents a token introspection request (RFC 7662), sent as a form.
+///
+/// The client authenticates with HTTP Basic, or with `client_id` and `client_secret`
+/// in the form.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct IntrospectionRequest {
+    /// The token to introspect.
+    #[schema(example = "eyJhbGciOiJIUzI1NiIsInR5cCI6IkpXVCJ9...")]
+    pub token: String,
+
+    /// A hint about the type of the token. Only access tokens are introspected, so it
+    /// is ignored.
+    #[schema(example = "access_token", nullable = true)]
+    pub token_type_hint: Option<String>,
+
+    /// The id of the calling client, when it doesn't use HTTP Basic.
+    #[schema(example = "gateway", nullable = true)]
+    pub client_id: Option<String>,
+
+    /// The secret of the calling client, when it doesn't use HTTP Basic.
+    #[schema(nullable = true)]
+    pub client_secret: Option<String>,
+}
+
+/// Trait that defines the expected behavior of any type representing a token introspection request.
+///
+/// Allows for flexibility in handling different input types while following the same interface.
+pub trait IntrospectionRequestLike {
+    /// Returns the token to introspect.
+    fn token(&self) -> &str;
+
+    /// Returns the client id sent in the body, if any.
+    fn client_id(&self) -> Option<&str>;
+
+    /// Returns the client secret sent in the body, if any.
+    fn client_secret(&self) -> Option<&str>;
+}
+
+/// Implements `IntrospectionRequestLike` for `IntrospectionRequest`,
+/// so it can be used where the trait is expected.
+impl IntrospectionRequestLike for IntrospectionRequest {
+    fn token(&self) -> &str {
+        &self.token
+    }
+
+    fn client_id(&self) -> Option<&str> {
+        self.client_id.as_deref()
+    }
+
+    fn client_secret(&self) -> Option<&str> {
+        self.client_secret.as_deref()
+    }
+}
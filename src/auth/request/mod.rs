@@ -1,3 +1,6 @@
+pub mod introspection_request;
 pub mod login_request;
 pub mod register_request;
 pub mod renew_request;
+pub mod revoke_request;
+pub mod token_exchange_request;
\ No newline at end of file
@@ -1,5 +1,6 @@
+use crate::auth::policy::password_policy::PasswordPolicy;
 use serde::{Deserialize, Serialize};
-use validator::Validate;
+use validator::{Validate, ValidationErrors};
 use utoipa::ToSchema;
 
 /// Represents a user registration request.
@@ -24,12 +25,31 @@ pub struct RegisterRequest {
 
     /// The password for the new account.
     ///
-    /// Must be between 4 and 30 characters.
-    #[validate(length(min = 4, max = 30))]
+    /// Not checked by [`Validate::validate`]: its bounds and rules come from a
+    /// [`PasswordPolicy`], see [`RegisterRequest::validate_with_policy`].
     #[schema(example = "securePass123")]
     pub password: String,
 }
 
+impl RegisterRequest {
+    /// Validates the username and email, and the password against `policy`.
+    ///
+    /// The errors of both are returned together, keyed by field.
+    pub fn validate_with_policy(&self, policy: &PasswordPolicy) -> Result<(), ValidationErrors> {
+        let mut errors = self.validate().err().unwrap_or_default();
+
+        if let Err(password_errors) = policy.validate_register(self) {
+            errors.errors_mut().extend(password_errors.into_errors());
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
 /// Trait that defines the expected behavior of any type representing a registration request.
 ///
 /// This allows using different implementations of registration input as long as they
@@ -0,0 +1,28 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Represents a token revocation request.
+///
+/// Typically sent on logout, or when a token is known to be compromised.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct RevokeRequest {
+    /// The token to revoke.
+    #[schema(example = "eyJhbGciOiJIUzI1NiIsInR5cCI6IkpXVCJ9...")]
+    pub token: String,
+}
+
+/// Trait that defines the expected behavior of any type representing a token revocation request.
+///
+/// Allows for flexibility in handling different input types while following the same interface.
+pub trait RevokeRequestLike {
+    /// Returns the token to revoke.
+    fn token(&self) -> &str;
+}
+
+/// Implements `RevokeRequestLike` for `RevokeRequest`,
+/// so it can be used where the trait is expected.
+impl RevokeRequestLike for RevokeRequest {
+    fn token(&self) -> &str {
+        &self.token
+    }
+}
@@ -0,0 +1,121 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// The `grant_type` of a token exchange request.
+pub const TOKEN_EXCHANGE_GRANT_TYPE: &str = "urn:ietf:params:oauth:grant-type:token-exchange";
+
+/// The token type of an access token, like the short token.
+pub const ACCESS_TOKEN_TYPE: &str = "urn:ietf:params:oauth:token-type:access_token";
+
+/// The token type of a JWT.
+pub const JWT_TOKEN_TYPE: &str = "urn:ietf:params:oauth:token-type:jwt";
+
+/// Represents a token exchange request (RFC 8693), sent as a form.
+///
+/// The client authenticates with HTTP Basic, or with `client_id` and `client_secret`
+/// in the form, and becomes the actor of the issued token. Actor tokens are not
+/// supported.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct TokenExchangeRequest {
+    /// Must be `urn:ietf:params:oauth:grant-type:token-exchange`.
+    #[schema(example = "urn:ietf:params:oauth:grant-type:token-exchange")]
+    pub grant_type: String,
+
+    /// The token to exchange, usually the user's short token.
+    #[schema(example = "eyJhbGciOiJIUzI1NiIsInR5cCI6IkpXVCJ9...")]
+    pub subject_token: String,
+
+    /// The type of the subject token, an access token or a JWT.
+    #[schema(example = "urn:ietf:params:oauth:token-type:access_token")]
+    pub subject_token_type: String,
+
+    /// The service the issued token is for. Only one audience per request is
+    /// supported.
+    #[schema(example = "billing", nullable = true)]
+    pub audience: Option<String>,
+
+    /// The scopes of the issued token, separated by spaces. Defaults to those of the
+    /// subject token.
+    #[schema(example = "read", nullable = true)]
+    pub scope: Option<String>,
+
+    /// The type of the token to issue. Only access tokens are issued.
+    #[schema(
+        example = "urn:ietf:params:oauth:token-type:access_token",
+        nullable = true
+    )]
+    pub requested_token_type: Option<String>,
+
+    /// The id of the calling client, when it doesn't use HTTP Basic.
+    #[schema(example = "edge", nullable = true)]
+    pub client_id: Option<String>,
+
+    /// The secret of the calling client, when it doesn't use HTTP Basic.
+    #[schema(nullable = true)]
+    pub client_secret: Option<String>,
+}
+
+/// Trait that defines the expected behavior of any type representing a token exchange request.
+///
+/// Allows for flexibility in handling different input types while following the same interface.
+pub trait TokenExchangeRequestLike {
+    /// Returns the grant type.
+    fn grant_type(&self) -> &str;
+
+    /// Returns the token to exchange.
+    fn subject_token(&self) -> &str;
+
+    /// Returns the type of the token to exchange.
+    fn subject_token_type(&self) -> &str;
+
+    /// Returns the service the issued token is for, if any.
+    fn audience(&self) -> Option<&str>;
+
+    /// Returns the requested scopes, if any.
+    fn scope(&self) -> Option<&str>;
+
+    /// Returns the requested token type, if any.
+    fn requested_token_type(&self) -> Option<&str>;
+
+    /// Returns the client id sent in the body, if any.
+    fn client_id(&self) -> Option<&str>;
+
+    /// Returns the client secret sent in the body, if any.
+    fn client_secret(&self) -> Option<&str>;
+}
+
+/// Implements `TokenExchangeRequestLike` for `TokenExchangeRequest`,
+/// so it can be used where the trait is expected.
+impl TokenExchangeRequestLike for TokenExchangeRequest {
+    fn grant_type(&self) -> &str {
+        &self.grant_type
+    }
+
+    fn subject_token(&self) -> &str {
+        &self.subject_token
+    }
+
+    fn subject_token_type(&self) -> &str {
+        &self.subject_token_type
+    }
+
+    fn audience(&self) -> Option<&str> {
+        self.audience.as_deref()
+    }
+
+    fn scope(&self) -> Option<&str> {
+        self.scope.as_deref()
+    }
+
+    fn requested_token_type(&self) -> Option<&str> {
+        self.requested_token_type.as_deref()
+    }
+
+    fn client_id(&self) -> Option<&str> {
+        self.client_id.as_deref()
+    }
+
+    fn client_secret(&self) -> Option<&str> {
+        self.client_secret.as_deref()
+    }
+}
@@ -0,0 +1,152 @@
+use crate::auth::service::token_claims::{JwtDataContainer, audience};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use utoipa::ToSchema;
+
+/// The state of a token, as returned by the introspection endpoint (RFC 7662).
+///
+/// An inactive token only has `"active": false`: the reason it is inactive (expired,
+/// revoked, forged) is not disclosed.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct IntrospectionResponse {
+    /// Whether the token is valid, unexpired and not revoked.
+    #[schema(example = true)]
+    pub active: bool,
+
+    /// The scopes of the token, separated by spaces. Taken from a `scope` string or a
+    /// `scopes` array in the token data.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(example = "read write", nullable = true)]
+    pub scope: Option<String>,
+
+    /// The `client_id` in the token data, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = true)]
+    pub client_id: Option<String>,
+
+    /// The `username` in the token data, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(example = "lunna", nullable = true)]
+    pub username: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(example = "Bearer", nullable = true)]
+    pub token_type: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = true)]
+    pub exp: Option<u64>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = true)]
+    pub iat: Option<u64>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = true)]
+    pub nbf: Option<u64>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = true)]
+    pub sub: Option<String>,
+
+    /// A single audience is sent as a string, several as an array.
+    #[serde(default, skip_serializing_if = "Vec::is_empty", with = "audience")]
+    #[schema(value_type = Object)]
+    pub aud: Vec<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = true)]
+    pub iss: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(nullable = true)]
+    pub jti: Option<String>,
+}
+
+impl IntrospectionResponse {
+    /// The response for a token that isn't active.
+    pub fn inactive() -> IntrospectionResponse {
+        IntrospectionResponse::default()
+    }
+}
+
+impl From<JwtDataContainer<Value>> for IntrospectionResponse {
+    fn from(container: JwtDataContainer<Value>) -> Self {
+        let string = |key: &str| {
+            container
+                .data
+                .get(key)
+                .and_then(Value::as_str)
+                .map(str::to_string)
+        };
+        let scope = string("scope").or_else(|| {
+            container
+                .data
+                .get("scopes")
+                .and_then(Value::as_array)
+                .map(|scopes| {
+                    scopes
+                        .iter()
+                        .filter_map(Value::as_str)
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                })
+        });
+
+        IntrospectionResponse {
+            active: true,
+            scope,
+            client_id: string("client_id"),
+            username: string("username"),
+            token_type: Some("Bearer".to_string()),
+            exp: Some(container.expires_at()),
+            iat: container.issued_at(),
+            nbf: container.not_before(),
+            sub: container.subject().map(str::to_string),
+            aud: container.audience().to_vec(),
+            iss: container.issuer().map(str::to_string),
+            jti: container.jwt_id().map(str::to_string),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_inactive_only_has_active() {
+        assert_eq!(
+            serde_json::to_value(IntrospectionResponse::inactive()).unwrap(),
+            json!({"active": false})
+        );
+    }
+
+    #[test]
+    fn test_from_container() {
+        let container = JwtDataContainer::new(
+            json!({"username": "lunna", "scopes": ["read", "write"]}),
+            1_700_000_060,
+        )
+        .with_issuer("https://auth.lunna.dev")
+        .with_subject("42")
+        .with_audience("gateway")
+        .with_jwt_id("3f2a");
+
+        assert_eq!(
+            serde_json::to_value(IntrospectionResponse::from(container)).unwrap(),
+            json!({
+                "active": true,
+                "scope": "read write",
+                "username": "lunna",
+                "token_type": "Bearer",
+                "exp": 1_700_000_060,
+                "sub": "42",
+                "aud": "gateway",
+                "iss": "https://auth.lunna.dev",
+                "jti": "3f2a",
+            })
+        );
+    }
+}
@@ -0,0 +1,23 @@
+use jsonwebtoken::jwk::{Jwk, JwkSet};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// A JSON Web Key Set (RFC 7517) with the public keys used to verify tokens.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct JwksResponse {
+    /// ## Keys
+    ///
+    /// One JWK per verification key, with its `kid`, `alg` and `use` (always `sig`).  
+    /// Pick the key whose `kid` matches the token header.
+    #[schema(
+        value_type = Vec<Object>,
+        example = json!([{"kty": "RSA", "use": "sig", "alg": "RS256", "kid": "default", "n": "...", "e": "AQAB"}])
+    )]
+    pub keys: Vec<Jwk>,
+}
+
+impl From<JwkSet> for JwksResponse {
+    fn from(set: JwkSet) -> Self {
+        JwksResponse { keys: set.keys }
+    }
+}
@@ -1 +1,6 @@
-pub mod token_response;
\ No newline at end of file
+pub mod token_response;
+#[cfg(feature = "jwt")]
+pub mod jwks_response;
+pub mod token_cookie_response;
+pub mod introspection_response;
+pub mod token_exchange_response;
@@ -0,0 +1,132 @@
+use crate::auth::response::token_response::TokenResponse;
+use crate::auth::service::token_cookie_config::TokenCookieConfig;
+use actix_web::body::BoxBody;
+use actix_web::{HttpRequest, HttpResponse, Responder};
+
+/// Sends a [`TokenResponse`] to a browser as cookies instead of a JSON body.
+///
+/// The response is `204 No Content` with the cookies described by the
+/// `web::Data<TokenCookieConfig>` of the app, or the default one. The tokens never
+/// reach the frontend's scripts, only the CSRF token does.
+///
+/// Use [`TokenCookieResponse::new`] on login or registration, which mints the CSRF
+/// token, and [`TokenCookieResponse::renewed`] on renew, which keeps it.
+///
+/// # Example
+/// ```
+/// use actix_web::post;
+/// use lunna_actix_utils::auth::response::token_cookie_response::TokenCookieResponse;
+/// use lunna_actix_utils::auth::response::token_response::TokenResponse;
+///
+/// #[post("/login")]
+/// async fn login() -> TokenCookieResponse {
+///     let tokens = TokenResponse {
+///         long_token: Some("<the long token>".to_string()),
+///         short_token: "<the short token>".to_string(),
+///     };
+///     TokenCookieResponse::new(tokens)
+/// }
+///
+/// #[post("/renew")]
+/// async fn renew() -> TokenCookieResponse {
+///     let tokens = TokenResponse {
+///         long_token: Some("<the rotated long token>".to_string()),
+///         short_token: "<the short token>".to_string(),
+///     };
+///     TokenCookieResponse::renewed(tokens)
+/// }
+///
+/// #[post("/logout")]
+/// async fn logout() -> TokenCookieResponse {
+///     TokenCookieResponse::clear()
+/// }
+/// ```
+pub struct TokenCookieResponse {
+    tokens: Option<TokenResponse>,
+    renewed: bool,
+}
+
+impl TokenCookieResponse {
+    /// Sets the token cookies and a fresh CSRF token, for login or registration.
+    pub fn new(tokens: TokenResponse) -> TokenCookieResponse {
+        TokenCookieResponse {
+            tokens: Some(tokens),
+            renewed: false,
+        }
+    }
+
+    /// Sets the token cookies and keeps the CSRF token, for renew.
+    pub fn renewed(tokens: TokenResponse) -> TokenCookieResponse {
+        TokenCookieResponse {
+            tokens: Some(tokens),
+            renewed: true,
+        }
+    }
+
+    /// Removes every token cookie, for logout.
+    pub fn clear() -> TokenCookieResponse {
+        TokenCookieResponse {
+            tokens: None,
+            renewed: false,
+        }
+    }
+}
+
+impl From<TokenResponse> for TokenCookieResponse {
+    fn from(tokens: TokenResponse) -> Self {
+        TokenCookieResponse::new(tokens)
+    }
+}
+
+impl Responder for TokenCookieResponse {
+    type Body = BoxBody;
+
+    fn respond_to(self, req: &HttpRequest) -> HttpResponse {
+        let config = TokenCookieConfig::of(req);
+        let cookies = match &self.tokens {
+            Some(tokens) if self.renewed => config.renew_cookies(tokens),
+            Some(tokens) => config.cookies(tokens),
+            None => config.removal_cookies(),
+        };
+
+        let mut response = HttpResponse::NoContent();
+        for cookie in cookies {
+            response.cookie(cookie);
+        }
+
+        response.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+
+    fn tokens(long_token: &str) -> TokenResponse {
+        TokenResponse {
+            long_token: Some(long_token.to_string()),
+            short_token: "short".to_string(),
+        }
+    }
+
+    fn cookie_names(response: HttpResponse) -> Vec<String> {
+        response
+            .cookies()
+            .map(|cookie| cookie.name().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_renew_keeps_csrf_token() {
+        let req = TestRequest::default().to_http_request();
+
+        let login = TokenCookieResponse::new(tokens("long")).respond_to(&req);
+        assert!(cookie_names(login).contains(&"csrf_token".to_string()));
+
+        for long_token in ["rotated", "rotated again"] {
+            let renew = TokenCookieResponse::renewed(tokens(long_token)).respond_to(&req);
+            assert_eq!(cookie_names(renew), ["short_token", "long_token"]);
+        }
+    }
+}
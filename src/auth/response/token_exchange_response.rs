@@ -0,0 +1,41 @@
+use crate::auth::request::token_exchange_request::ACCESS_TOKEN_TYPE;
+use crate::auth::service::token_exchange_service::ExchangedToken;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// The token issued by a token exchange (RFC 8693).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct TokenExchangeResponse {
+    /// The issued token, valid for the requested audience only.
+    #[schema(example = "eyJhbGciOiJIUzI1NiIsInR5cCI6IkpXVCJ9...")]
+    pub access_token: String,
+
+    /// Always `urn:ietf:params:oauth:token-type:access_token`.
+    #[schema(example = "urn:ietf:params:oauth:token-type:access_token")]
+    pub issued_token_type: String,
+
+    /// Always `Bearer`.
+    #[schema(example = "Bearer")]
+    pub token_type: String,
+
+    /// Seconds until the issued token expires.
+    #[schema(example = 300)]
+    pub expires_in: u64,
+
+    /// The scopes of the issued token, separated by spaces.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(example = "read", nullable = true)]
+    pub scope: Option<String>,
+}
+
+impl From<ExchangedToken> for TokenExchangeResponse {
+    fn from(token: ExchangedToken) -> Self {
+        TokenExchangeResponse {
+            access_token: token.token,
+            issued_token_type: ACCESS_TOKEN_TYPE.to_string(),
+            token_type: "Bearer".to_string(),
+            expires_in: token.expires_in,
+            scope: token.scope,
+        }
+    }
+}
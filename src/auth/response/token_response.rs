@@ -4,14 +4,21 @@ use utoipa::ToSchema;
 /// Represents the response containing authentication tokens.
 ///
 /// Contains both a long-lived token and a short-lived token.
-/// The `long_token` is only returned during login or registration.
-#[derive(Debug, Serialize, Deserialize, ToSchema)]
+/// The `long_token` is returned during login or registration, and on renew when it is
+/// rotated, as [`RefreshTokenService`](crate::auth::service::refresh_token_service::RefreshTokenService)
+/// does.
+///
+/// Browser frontends should get the tokens as cookies instead, with
+/// [`TokenCookieResponse`](super::token_cookie_response::TokenCookieResponse), so that
+/// an injected script can't steal them.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct TokenResponse {
     /// ## Long token
     ///
-    /// The long token is stored in the local storage and in the database.  
+    /// The long token is stored by the client and in the database.  
     /// This token is used to authenticate the user and generate the short token.  
-    /// It is **only** present in the response when the user logs in or registers.
+    /// It is present in the response when the user logs in or registers, and on renew
+    /// when the long token is rotated.
     #[schema(
         example = "<the token>",
         nullable = true
@@ -20,7 +27,7 @@ pub struct TokenResponse {
 
     /// ## Short token
     ///
-    /// The short token is also stored in local storage, but not in the database.  
+    /// The short token is also stored by the client, but not in the database.  
     /// It is used to authenticate the user in microservices and is valid for a short period of time.  
     /// It is generated by the long token and signed with a secret key, which is shared among microservices for verification.
     #[schema(example = "eyJhbGciOiJIUzI1NiIsInR5cCI6IkpXVCJ9.shorttoken...")]
@@ -4,11 +4,16 @@ use crate::auth::{
     error::AuthError,
     request::{
         login_request::LoginRequestLike, register_request::RegisterRequestLike,
-        renew_request::RenewRequestLike,
+        renew_request::RenewRequestLike, revoke_request::RevokeRequestLike,
     },
     response::token_response::TokenResponse,
 };
 
+/// The authentication flows of an application.
+///
+/// Implementations issue and check short tokens through an `Arc<dyn TokenCodec>`
+/// (see [`TokenCodec`](super::token_codec::TokenCodec)) rather than a concrete
+/// service, so switching between JWT and PASETO is a matter of configuration.
 #[async_trait]
 pub trait AuthService: Send + Sync {
     async fn login(&self, login_request: &dyn LoginRequestLike)
@@ -19,6 +24,56 @@ pub trait AuthService: Send + Sync {
         register_request: &dyn RegisterRequestLike,
     ) -> Result<TokenResponse, AuthError>;
 
+    /// Exchanges a long token for a new short token and a new long token.
+    ///
+    /// Long tokens are single-use: the presented one is consumed, usually through
+    /// [`RefreshTokenService::rotate`](super::refresh_token_service::RefreshTokenService::rotate),
+    /// so presenting it again fails with [`AuthError::TokenReused`] and revokes every
+    /// token rotated from the same login.
     async fn renew(&self, renew_request: &dyn RenewRequestLike)
     -> Result<TokenResponse, AuthError>;
+
+    /// Revokes a token so it is rejected until it expires, usually through
+    /// [`TokenCodec::revoke`](super::token_codec::TokenCodec::revoke).
+    ///
+    /// Fails with [`AuthError::InternalError`] unless implemented, so services written
+    /// before revocation existed keep compiling.
+    async fn revoke(&self, _revoke_request: &dyn RevokeRequestLike) -> Result<(), AuthError> {
+        Err(AuthError::InternalError)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::request::revoke_request::RevokeRequest;
+
+    struct NoRevocation;
+
+    #[async_trait]
+    impl AuthService for NoRevocation {
+        async fn login(&self, _: &dyn LoginRequestLike) -> Result<TokenResponse, AuthError> {
+            Err(AuthError::InvalidUsernameOrPassword)
+        }
+
+        async fn register(&self, _: &dyn RegisterRequestLike) -> Result<TokenResponse, AuthError> {
+            Err(AuthError::InvalidUsernameOrPassword)
+        }
+
+        async fn renew(&self, _: &dyn RenewRequestLike) -> Result<TokenResponse, AuthError> {
+            Err(AuthError::TokenNotFound)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_revoke_defaults_to_an_error() {
+        let request = RevokeRequest {
+            token: "token".to_string(),
+        };
+
+        assert!(matches!(
+            NoRevocation.revoke(&request).await,
+            Err(AuthError::InternalError)
+        ));
+    }
 }
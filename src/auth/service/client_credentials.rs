@@ -0,0 +1,289 @@
+use crate::auth::error::AuthError;
+use crate::auth::service::hash_service::HashService;
+use actix_web::HttpRequest;
+use actix_web::http::header::AUTHORIZATION;
+use async_trait::async_trait;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use percent_encoding::percent_decode_str;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::OnceCell;
+
+/// The `client_id` and `client_secret` a client authenticates with (RFC 6749
+/// section 2.3.1).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientCredentials {
+    pub client_id: String,
+    pub client_secret: String,
+}
+
+impl ClientCredentials {
+    pub fn new(client_id: &str, client_secret: &str) -> ClientCredentials {
+        ClientCredentials {
+            client_id: client_id.to_string(),
+            client_secret: client_secret.to_string(),
+        }
+    }
+
+    /// Reads the credentials from an `Authorization: Basic` header, or else from the
+    /// `client_id` and `client_secret` of the request body.
+    ///
+    /// Sending them both ways at once is rejected, as RFC 6749 asks.
+    pub(crate) fn from_request(
+        req: &HttpRequest,
+        client_id: Option<&str>,
+        client_secret: Option<&str>,
+    ) -> Result<ClientCredentials, AuthError> {
+        let Some(header) = req.headers().get(AUTHORIZATION) else {
+            return match (client_id, client_secret) {
+                (Some(client_id), Some(client_secret)) => {
+                    Ok(ClientCredentials::new(client_id, client_secret))
+                }
+                _ => Err(AuthError::InvalidClient),
+            };
+        };
+
+        if client_id.is_some() || client_secret.is_some() {
+            return Err(AuthError::InvalidClient);
+        }
+
+        let credentials = header
+            .to_str()
+            .ok()
+            .and_then(|header| header.split_once(' '))
+            .filter(|(scheme, _)| scheme.eq_ignore_ascii_case("Basic"))
+            .and_then(|(_, credentials)| STANDARD.decode(credentials.trim()).ok())
+            .and_then(|credentials| String::from_utf8(credentials).ok())
+            .ok_or(AuthError::InvalidClient)?;
+        let (client_id, client_secret) = credentials
+            .split_once(':')
+            .ok_or(AuthError::InvalidClient)?;
+
+        Ok(ClientCredentials {
+            client_id: form_decode(client_id)?,
+            client_secret: form_decode(client_secret)?,
+        })
+    }
+}
+
+/// Basic credentials are form encoded before they are base64 encoded.
+fn form_decode(value: &str) -> Result<String, AuthError> {
+    percent_decode_str(&value.replace('+', " "))
+        .decode_utf8()
+        .map(|value| value.into_owned())
+        .map_err(|_| AuthError::InvalidClient)
+}
+
+/// Where the registered OAuth2 clients are kept.
+#[async_trait]
+pub trait ClientStore: Send + Sync {
+    /// Returns the hashed secret of a client, as made by
+    /// [`HashService::hash_password`], or `None` if there is no such client.
+    async fn secret_hash(&self, client_id: &str) -> Result<Option<String>, AuthError>;
+}
+
+/// A [`ClientStore`] kept in memory, for clients known when the app starts.
+///
+/// # Example
+/// ```
+/// use lunna_actix_utils::auth::service::client_credentials::{ClientStore, InMemoryClientStore};
+/// use lunna_actix_utils::auth::service::hash_service::HashService;
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let hash = HashService::new().hash_password("gateway secret").unwrap();
+/// let store = InMemoryClientStore::new().with_client("gateway", &hash);
+///
+/// assert!(store.secret_hash("gateway").await.unwrap().is_some());
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryClientStore {
+    clients: HashMap<String, String>,
+}
+
+impl InMemoryClientStore {
+    pub fn new() -> InMemoryClientStore {
+        InMemoryClientStore::default()
+    }
+
+    /// Registers a client with its hashed secret.
+    pub fn with_client(mut self, client_id: &str, secret_hash: &str) -> Self {
+        self.clients
+            .insert(client_id.to_string(), secret_hash.to_string());
+        self
+    }
+}
+
+#[async_trait]
+impl ClientStore for InMemoryClientStore {
+    async fn secret_hash(&self, client_id: &str) -> Result<Option<String>, AuthError> {
+        Ok(self.clients.get(client_id).cloned())
+    }
+}
+
+/// Checks client credentials against a [`ClientStore`].
+///
+/// The OAuth2 handlers expect it as `web::Data<ClientAuthenticator>`.
+#[derive(Clone)]
+pub struct ClientAuthenticator {
+    store: Arc<dyn ClientStore>,
+    hash_service: HashService,
+    dummy_hash: Arc<OnceCell<String>>,
+}
+
+impl ClientAuthenticator {
+    pub fn new(store: Arc<dyn ClientStore>, hash_service: HashService) -> ClientAuthenticator {
+        ClientAuthenticator {
+            store,
+            hash_service,
+            dummy_hash: Arc::new(OnceCell::new()),
+        }
+    }
+
+    /// Returns [`AuthError::InvalidClient`] unless the client exists and the secret
+    /// matches.
+    ///
+    /// The secret of an unknown client is still verified, against a dummy hash, so that
+    /// both take as long and client ids can't be enumerated by timing.
+    pub async fn authenticate(&self, credentials: &ClientCredentials) -> Result<(), AuthError> {
+        let secret_hash = self.store.secret_hash(&credentials.client_id).await?;
+        let known = secret_hash.is_some();
+        let secret_hash = match secret_hash {
+            Some(secret_hash) => secret_hash,
+            None => self.dummy_hash().await?,
+        };
+
+        let verification = self
+            .hash_service
+            .verify_password_async(&credentials.client_secret, &secret_hash)
+            .await?;
+
+        if known && verification.is_valid() {
+            Ok(())
+        } else {
+            Err(AuthError::InvalidClient)
+        }
+    }
+
+    /// A hash made with the current configuration, so that verifying against it costs
+    /// the same as against a client's. Made on the first unknown client.
+    async fn dummy_hash(&self) -> Result<String, AuthError> {
+        self.dummy_hash
+            .get_or_try_init(|| self.hash_service.hash_password_async("dummy client secret"))
+            .await
+            .cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+
+    fn authenticator() -> ClientAuthenticator {
+        let hash_service = HashService::new();
+        let hash = hash_service.hash_password("gateway secret").unwrap();
+
+        ClientAuthenticator::new(
+            Arc::new(InMemoryClientStore::new().with_client("gateway", &hash)),
+            hash_service,
+        )
+    }
+
+    fn basic(client_id: &str, client_secret: &str) -> String {
+        format!(
+            "Basic {}",
+            STANDARD.encode(format!("{client_id}:{client_secret}"))
+        )
+    }
+
+    #[test]
+    fn test_credentials_are_read_from_the_basic_header() {
+        let req = TestRequest::default()
+            .insert_header((AUTHORIZATION, basic("my+gateway", "a%3Ab")))
+            .to_http_request();
+
+        assert_eq!(
+            ClientCredentials::from_request(&req, None, None).unwrap(),
+            ClientCredentials::new("my gateway", "a:b")
+        );
+    }
+
+    #[test]
+    fn test_credentials_are_read_from_the_body() {
+        let req = TestRequest::default().to_http_request();
+
+        assert_eq!(
+            ClientCredentials::from_request(&req, Some("gateway"), Some("secret")).unwrap(),
+            ClientCredentials::new("gateway", "secret")
+        );
+        assert!(matches!(
+            ClientCredentials::from_request(&req, Some("gateway"), None),
+            Err(AuthError::InvalidClient)
+        ));
+    }
+
+    #[test]
+    fn test_credentials_sent_twice_are_rejected() {
+        let req = TestRequest::default()
+            .insert_header((AUTHORIZATION, basic("gateway", "secret")))
+            .to_http_request();
+
+        assert!(matches!(
+            ClientCredentials::from_request(&req, Some("gateway"), Some("secret")),
+            Err(AuthError::InvalidClient)
+        ));
+    }
+
+    #[test]
+    fn test_non_basic_header_is_rejected() {
+        let req = TestRequest::default()
+            .insert_header((AUTHORIZATION, "Bearer abc"))
+            .to_http_request();
+
+        assert!(matches!(
+            ClientCredentials::from_request(&req, None, None),
+            Err(AuthError::InvalidClient)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_authenticate() {
+        let authenticator = authenticator();
+
+        assert!(
+            authenticator
+                .authenticate(&ClientCredentials::new("gateway", "gateway secret"))
+                .await
+                .is_ok()
+        );
+        assert!(matches!(
+            authenticator
+                .authenticate(&ClientCredentials::new("gateway", "wrong"))
+                .await,
+            Err(AuthError::InvalidClient)
+        ));
+        assert!(matches!(
+            authenticator
+                .authenticate(&ClientCredentials::new("unknown", "gateway secret"))
+                .await,
+            Err(AuthError::InvalidClient)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_unknown_client_is_verified_against_a_dummy_hash() {
+        let authenticator = authenticator();
+        assert!(!authenticator.dummy_hash.initialized());
+
+        assert!(matches!(
+            authenticator
+                .authenticate(&ClientCredentials::new("unknown", "dummy client secret"))
+                .await,
+            Err(AuthError::InvalidClient)
+        ));
+        assert!(authenticator.dummy_hash.initialized());
+    }
+}
@@ -0,0 +1,62 @@
+use chrono::Utc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// The source of the current time for issuing and checking tokens.
+///
+/// [`JwtService`](super::jwt_service::JwtService) reads the time only through its clock,
+/// for `iat` on new tokens and for `exp`, `nbf` and leeway on incoming ones, so expiry
+/// can be tested with a [`MockClock`] instead of waiting or hard-coding stale tokens.
+pub trait Clock: Send + Sync {
+    /// Seconds since the Unix epoch.
+    fn now(&self) -> u64;
+}
+
+/// The system clock, used unless another one is configured.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> u64 {
+        Utc::now().timestamp().max(0) as u64
+    }
+}
+
+/// A clock that only moves when told to.
+///
+/// # Example
+/// ```
+/// use lunna_actix_utils::auth::service::clock::{Clock, MockClock};
+/// use std::time::Duration;
+///
+/// let clock = MockClock::new(1_700_000_000);
+/// clock.advance(Duration::from_secs(60));
+///
+/// assert_eq!(clock.now(), 1_700_000_060);
+/// ```
+#[derive(Debug, Default)]
+pub struct MockClock {
+    now: AtomicU64,
+}
+
+impl MockClock {
+    pub fn new(now: u64) -> MockClock {
+        MockClock {
+            now: AtomicU64::new(now),
+        }
+    }
+
+    pub fn set(&self, now: u64) {
+        self.now.store(now, Ordering::SeqCst);
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        self.now.fetch_add(duration.as_secs(), Ordering::SeqCst);
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> u64 {
+        self.now.load(Ordering::SeqCst)
+    }
+}
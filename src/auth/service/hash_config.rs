@@ -0,0 +1,139 @@
+use crate::auth::service::pepper_keyring::PepperKeyring;
+use argon2::{Algorithm, KeyId, Params, ParamsBuilder, password_hash};
+use serde::{Deserialize, Serialize};
+
+/// The Argon2 variant used to hash new passwords.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HashAlgorithm {
+    Argon2d,
+    Argon2i,
+    #[default]
+    Argon2id,
+}
+
+impl From<HashAlgorithm> for Algorithm {
+    fn from(algorithm: HashAlgorithm) -> Self {
+        match algorithm {
+            HashAlgorithm::Argon2d => Algorithm::Argon2d,
+            HashAlgorithm::Argon2i => Algorithm::Argon2i,
+            HashAlgorithm::Argon2id => Algorithm::Argon2id,
+        }
+    }
+}
+
+/// Configuration for [`HashService`](super::hash_service::HashService).
+///
+/// Every field has a default matching the Argon2 recommendations, so a deployment
+/// only needs to set the values it wants to change.
+///
+/// # Example
+/// ```
+/// use lunna_actix_utils::auth::service::hash_config::{HashAlgorithm, HashServiceConfig};
+///
+/// let config: HashServiceConfig = serde_json::from_str(r#"{ "memory_cost": 65536 }"#).unwrap();
+///
+/// assert_eq!(config.algorithm, HashAlgorithm::Argon2id);
+/// assert_eq!(config.memory_cost, 65536);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HashServiceConfig {
+    /// The Argon2 variant.
+    pub algorithm: HashAlgorithm,
+
+    /// Memory size in KiB.
+    pub memory_cost: u32,
+
+    /// Number of iterations.
+    pub time_cost: u32,
+
+    /// Degree of parallelism.
+    pub parallelism: u32,
+
+    /// The shared salt used by hashes created before per-password salts.
+    ///
+    /// Hashes made with it still verify, but are reported as needing a rehash.
+    pub legacy_salt: Option<String>,
+
+    /// Maximum number of hashes computed at the same time by the async methods.
+    ///
+    /// Defaults to the number of available CPUs. Must be at least 1.
+    pub max_concurrent_hashes: usize,
+
+    /// Server-side secrets mixed into the hashes.
+    ///
+    /// Never serialized, so the config can be printed without leaking them.
+    #[serde(skip_serializing)]
+    pub peppers: PepperKeyring,
+}
+
+impl Default for HashServiceConfig {
+    fn default() -> Self {
+        HashServiceConfig {
+            algorithm: HashAlgorithm::default(),
+            memory_cost: Params::DEFAULT_M_COST,
+            time_cost: Params::DEFAULT_T_COST,
+            parallelism: Params::DEFAULT_P_COST,
+            legacy_salt: None,
+            max_concurrent_hashes: std::thread::available_parallelism()
+                .map(usize::from)
+                .unwrap_or(1),
+            peppers: PepperKeyring::default(),
+        }
+    }
+}
+
+impl HashServiceConfig {
+    pub fn with_algorithm(mut self, algorithm: HashAlgorithm) -> Self {
+        self.algorithm = algorithm;
+        self
+    }
+
+    pub fn with_memory_cost(mut self, memory_cost: u32) -> Self {
+        self.memory_cost = memory_cost;
+        self
+    }
+
+    pub fn with_time_cost(mut self, time_cost: u32) -> Self {
+        self.time_cost = time_cost;
+        self
+    }
+
+    pub fn with_parallelism(mut self, parallelism: u32) -> Self {
+        self.parallelism = parallelism;
+        self
+    }
+
+    pub fn with_legacy_salt(mut self, salt: &str) -> Self {
+        self.legacy_salt = Some(salt.to_string());
+        self
+    }
+
+    pub fn with_max_concurrent_hashes(mut self, max_concurrent_hashes: usize) -> Self {
+        self.max_concurrent_hashes = max_concurrent_hashes;
+        self
+    }
+
+    pub fn with_peppers(mut self, peppers: PepperKeyring) -> Self {
+        self.peppers = peppers;
+        self
+    }
+
+    /// Builds the Argon2 parameters, failing if any cost is out of range.
+    ///
+    /// The id of the current pepper, if any, is included as the `keyid`.
+    pub fn params(&self) -> Result<Params, password_hash::Error> {
+        let mut builder = ParamsBuilder::new();
+        builder
+            .m_cost(self.memory_cost)
+            .t_cost(self.time_cost)
+            .p_cost(self.parallelism);
+
+        if let Some(pepper) = self.peppers.current() {
+            builder.keyid(KeyId::new(pepper.id.as_bytes())?);
+        }
+
+        Ok(builder.build()?)
+    }
+}
@@ -1,50 +1,338 @@
+use crate::auth::error::AuthError;
+use crate::auth::service::hash_config::HashServiceConfig;
+use crate::auth::service::legacy_hash::{self, HashFormat};
+use crate::auth::service::pepper_keyring::{Pepper, PepperKeyring};
 use argon2::password_hash::SaltString;
-use argon2::{password_hash, Argon2, PasswordHash, PasswordHasher, PasswordVerifier};
-use std::sync::{Arc, Mutex};
+use argon2::password_hash::errors::InvalidValue;
+use argon2::password_hash::rand_core::OsRng;
+use argon2::{
+    Algorithm, Argon2, KeyId, Params, PasswordHash, PasswordHasher, PasswordVerifier, Version,
+    password_hash,
+};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
 
+/// The outcome of checking a password against a stored hash.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PasswordVerification {
+    /// The password does not match the stored hash.
+    Invalid,
+    /// The password matches and the stored hash is up to date.
+    Valid,
+    /// The password matches, but the stored hash should be replaced by
+    /// a fresh one from [`HashService::hash_password`].
+    NeedsRehash,
+}
+
+impl PasswordVerification {
+    /// Returns `true` if the password matched, whether or not a rehash is needed.
+    pub fn is_valid(&self) -> bool {
+        !matches!(self, PasswordVerification::Invalid)
+    }
+
+    /// Returns `true` if the password matched but the stored hash is outdated.
+    pub fn needs_rehash(&self) -> bool {
+        matches!(self, PasswordVerification::NeedsRehash)
+    }
+}
+
+/// Hashes and verifies passwords with Argon2.
+///
+/// Every call to [`HashService::hash_password`] uses a fresh random salt, which is
+/// stored in the resulting PHC string, so two users with the same password never
+/// share a hash. The Argon2 variant and costs come from a [`HashServiceConfig`].
+///
+/// If the config has a [`PepperKeyring`], new hashes are peppered with its current
+/// pepper and record its id, and stored hashes are verified with the pepper they name.
+///
+/// Hashing takes tens of milliseconds, so async code should prefer
+/// [`HashService::hash_password_async`] and [`HashService::verify_password_async`],
+/// which run on the blocking thread pool and are capped by
+/// [`HashServiceConfig::max_concurrent_hashes`]. Cloning the service is cheap and
+/// the clones share the same cap.
+#[derive(Clone)]
 pub struct HashService {
-    salt: Arc<Mutex<SaltString>>,
-    argon2: Argon2<'static>,
+    legacy_salt: Option<SaltString>,
+    algorithm: Algorithm,
+    params: Params,
+    peppers: PepperKeyring,
+    semaphore: Arc<Semaphore>,
+}
+
+impl Default for HashService {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl HashService {
-    pub fn new(salt: &str) -> HashService {
-        let salt = SaltString::encode_b64(salt.as_bytes()).expect("salt is invalid");
+    pub fn new() -> HashService {
+        Self::with_config(HashServiceConfig::default()).expect("default config is valid")
+    }
+
+    /// Creates a service that still recognizes hashes made with the old shared salt.
+    ///
+    /// Those hashes keep verifying, but are reported as
+    /// [`PasswordVerification::NeedsRehash`] so they can be replaced on the next login.
+    pub fn with_legacy_salt(salt: &str) -> HashService {
+        Self::with_config(HashServiceConfig::default().with_legacy_salt(salt))
+            .expect("salt is invalid")
+    }
+
+    /// Creates a service from a [`HashServiceConfig`], failing if the costs, the
+    /// legacy salt, the peppers or the concurrency cap are invalid.
+    pub fn with_config(config: HashServiceConfig) -> Result<HashService, password_hash::Error> {
+        config.peppers.validate()?;
+
+        // With no permits, every async hash would fail with `TooManyRequests`.
+        if config.max_concurrent_hashes == 0 {
+            return Err(password_hash::Error::ParamValueInvalid(
+                password_hash::errors::InvalidValue::TooShort,
+            ));
+        }
+
+        let legacy_salt = match &config.legacy_salt {
+            Some(salt) => Some(SaltString::encode_b64(salt.as_bytes())?),
+            None => None,
+        };
+        let algorithm = config.algorithm.into();
+        let params = config.params()?;
+
+        Ok(HashService {
+            legacy_salt,
+            algorithm,
+            params,
+            peppers: config.peppers,
+            semaphore: Arc::new(Semaphore::new(config.max_concurrent_hashes)),
+        })
+    }
+
+    /// Benchmarks this machine and returns Argon2id costs that hash in about `target`.
+    ///
+    /// Memory starts at `max_memory` KiB and is halved until a single iteration fits
+    /// in `target`. Then the number of iterations is raised as far as the target allows.
+    /// Run it in release mode on the deployment hardware; the `calibrate_hash` example
+    /// prints the result as config.
+    pub fn calibrate(
+        target: Duration,
+        max_memory: u32,
+    ) -> Result<HashServiceConfig, password_hash::Error> {
+        let mut config = HashServiceConfig::default()
+            .with_memory_cost(max_memory)
+            .with_time_cost(1);
+        let min_memory = Params::MIN_M_COST * config.parallelism;
 
-        HashService {
-            salt: Arc::new(Mutex::new(salt)),
-            argon2: Argon2::default(),
+        let mut elapsed = Self::measure(&config)?;
+        while elapsed > target && config.memory_cost / 2 >= min_memory {
+            config.memory_cost /= 2;
+            elapsed = Self::measure(&config)?;
         }
+
+        let iterations = target.as_secs_f64() / elapsed.as_secs_f64().max(f64::EPSILON);
+        config.time_cost = (iterations.floor() as u32).max(Params::MIN_T_COST);
+
+        while config.time_cost > Params::MIN_T_COST && Self::measure(&config)? > target {
+            config.time_cost -= 1;
+        }
+
+        Ok(config)
+    }
+
+    fn measure(config: &HashServiceConfig) -> Result<Duration, password_hash::Error> {
+        let service = HashService::with_config(config.clone())?;
+        let start = Instant::now();
+        service.hash_password("calibration password")?;
+        Ok(start.elapsed())
     }
 
     pub fn hash_password(&self, password: &str) -> Result<String, password_hash::Error> {
-        let salt = self.salt.lock().unwrap();
+        let salt = SaltString::generate(&mut OsRng);
+
+        let argon2 = self.argon2(self.peppers.current())?;
 
-        match self.argon2.hash_password(password.as_bytes(), &*salt) {
+        match argon2.hash_password(password.as_bytes(), &salt) {
             Ok(result) => Ok(result.to_string()),
             Err(err) => Err(err),
         }
     }
 
+    /// Verifies a password against a stored hash.
+    ///
+    /// Besides Argon2, hashes imported from older systems are accepted when the
+    /// matching cargo feature is enabled (see [`HashFormat`]). Those always verify as
+    /// [`PasswordVerification::NeedsRehash`].
     pub fn verify_password(
         &self,
         password: &str,
         hashed_password: &str,
-    ) -> Result<bool, password_hash::Error> {
+    ) -> Result<PasswordVerification, password_hash::Error> {
+        let format = HashFormat::detect(hashed_password);
+
+        if format != HashFormat::Argon2 {
+            return match legacy_hash::verify_legacy(format, password, hashed_password)? {
+                true => Ok(PasswordVerification::NeedsRehash),
+                false => Ok(PasswordVerification::Invalid),
+            };
+        }
+
         let parsed_hash = PasswordHash::new(hashed_password)?;
+        let argon2 = self.argon2(self.pepper_for(&parsed_hash)?)?;
 
-        let result = self
-            .argon2
+        if argon2
             .verify_password(password.as_bytes(), &parsed_hash)
-            .is_ok();
+            .is_err()
+        {
+            return Ok(PasswordVerification::Invalid);
+        }
+
+        if self.hash_needs_rehash(&parsed_hash) {
+            return Ok(PasswordVerification::NeedsRehash);
+        }
+
+        Ok(PasswordVerification::Valid)
+    }
 
-        Ok(result)
+    /// Like [`HashService::hash_password`], but runs on the blocking thread pool.
+    ///
+    /// Returns [`AuthError::TooManyRequests`] right away if
+    /// [`HashServiceConfig::max_concurrent_hashes`] hashes are already running.
+    pub async fn hash_password_async(&self, password: &str) -> Result<String, AuthError> {
+        let password = password.to_string();
+
+        self.run_blocking(move |service| service.hash_password(&password))
+            .await
+    }
+
+    /// Like [`HashService::verify_password`], but runs on the blocking thread pool.
+    ///
+    /// Returns [`AuthError::TooManyRequests`] right away if
+    /// [`HashServiceConfig::max_concurrent_hashes`] hashes are already running.
+    pub async fn verify_password_async(
+        &self,
+        password: &str,
+        hashed_password: &str,
+    ) -> Result<PasswordVerification, AuthError> {
+        let password = password.to_string();
+        let hashed_password = hashed_password.to_string();
+
+        self.run_blocking(move |service| service.verify_password(&password, &hashed_password))
+            .await
+    }
+
+    async fn run_blocking<T, F>(&self, f: F) -> Result<T, AuthError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&HashService) -> Result<T, password_hash::Error> + Send + 'static,
+    {
+        let permit = self
+            .semaphore
+            .clone()
+            .try_acquire_owned()
+            .map_err(|_| AuthError::TooManyRequests)?;
+        let service = self.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let result = f(&service);
+            drop(permit);
+            result
+        })
+        .await
+        .map_err(|_| AuthError::InternalError)?
+        .map_err(|_| AuthError::InternalError)
+    }
+
+    /// Returns `true` if a stored hash was not made with the current configuration.
+    ///
+    /// This covers a different Argon2 variant, version or costs, hashes made with the
+    /// legacy shared salt, and hashes whose pepper is missing, retired or unknown.
+    /// Anything that can't be parsed as a PHC string also needs a rehash.
+    pub fn needs_rehash(&self, hashed_password: &str) -> bool {
+        match PasswordHash::new(hashed_password) {
+            Ok(parsed_hash) => self.hash_needs_rehash(&parsed_hash),
+            Err(_) => true,
+        }
+    }
+
+    fn hash_needs_rehash(&self, hash: &PasswordHash) -> bool {
+        if Algorithm::try_from(hash.algorithm) != Ok(self.algorithm) {
+            return true;
+        }
+
+        if hash.version != Some(Version::V0x13.into()) {
+            return true;
+        }
+
+        let Ok(params) = Params::try_from(hash) else {
+            return true;
+        };
+
+        params.m_cost() != self.params.m_cost()
+            || params.t_cost() != self.params.t_cost()
+            || params.p_cost() != self.params.p_cost()
+            || self.uses_legacy_salt(hash)
+            || self.pepper_needs_rehash(&params)
+    }
+
+    fn pepper_needs_rehash(&self, params: &Params) -> bool {
+        if params.keyid().is_empty() {
+            return self.peppers.current().is_some();
+        }
+
+        match std::str::from_utf8(params.keyid())
+            .ok()
+            .and_then(|id| self.peppers.get(id))
+        {
+            Some(pepper) => pepper.retired,
+            None => true,
+        }
+    }
+
+    /// Finds the pepper named by the `keyid` of a stored hash.
+    fn pepper_for(&self, hash: &PasswordHash) -> Result<Option<&Pepper>, password_hash::Error> {
+        let Some(keyid) = hash.params.get_str("keyid") else {
+            return Ok(None);
+        };
+        let keyid = KeyId::from_b64(keyid)?;
+
+        std::str::from_utf8(keyid.as_bytes())
+            .ok()
+            .and_then(|id| self.peppers.get(id))
+            .map(Some)
+            .ok_or(password_hash::Error::ParamValueInvalid(
+                InvalidValue::Malformed,
+            ))
+    }
+
+    fn argon2<'a>(
+        &'a self,
+        pepper: Option<&'a Pepper>,
+    ) -> Result<Argon2<'a>, password_hash::Error> {
+        let params = self.params.clone();
+
+        match pepper {
+            Some(pepper) => Ok(Argon2::new_with_secret(
+                pepper.secret.as_bytes(),
+                self.algorithm,
+                Version::V0x13,
+                params,
+            )?),
+            None => Ok(Argon2::new(self.algorithm, Version::V0x13, params)),
+        }
+    }
+
+    fn uses_legacy_salt(&self, hash: &PasswordHash) -> bool {
+        match (&self.legacy_salt, hash.salt) {
+            (Some(legacy), Some(salt)) => legacy.as_str() == salt.as_str(),
+            _ => false,
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::auth::service::hash_config::HashAlgorithm;
     const SALT: &str = "z))IEw6Tph?7(TY83[`2";
 
     #[test]
@@ -52,7 +340,10 @@ mod tests {
         let service = get_hash_service();
         let password = "password123";
         let hash = service.hash_password(password).unwrap();
-        assert!(service.verify_password(password, &hash).unwrap());
+        assert_eq!(
+            service.verify_password(password, &hash).unwrap(),
+            PasswordVerification::Valid
+        );
     }
 
     #[test]
@@ -60,7 +351,12 @@ mod tests {
         let service = get_hash_service();
         let password = "password123";
         let hash = service.hash_password(password).unwrap();
-        assert!(!service.verify_password("wrongpassword", &hash).unwrap());
+        assert!(
+            !service
+                .verify_password("wrongpassword", &hash)
+                .unwrap()
+                .is_valid()
+        );
     }
 
     #[test]
@@ -68,16 +364,258 @@ mod tests {
         let service = get_hash_service();
         let password = "";
         let hash = service.hash_password(password).unwrap();
-        assert!(service.verify_password(password, &hash).unwrap());
+        assert!(service.verify_password(password, &hash).unwrap().is_valid());
     }
 
     #[test]
-    fn test_consistent_hashing() {
+    fn test_unique_salt_per_hash() {
         let service = get_hash_service();
         let password = "consistent_password";
         let hash1 = service.hash_password(password).unwrap();
         let hash2 = service.hash_password(password).unwrap();
-        assert_eq!(hash1, hash2);
+        assert_ne!(hash1, hash2);
+        assert!(
+            service
+                .verify_password(password, &hash1)
+                .unwrap()
+                .is_valid()
+        );
+        assert!(
+            service
+                .verify_password(password, &hash2)
+                .unwrap()
+                .is_valid()
+        );
+    }
+
+    #[test]
+    fn test_legacy_salt_hash_needs_rehash() {
+        let service = get_hash_service();
+        let password = "legacy_password";
+        let hash = legacy_hash(password);
+
+        assert_eq!(
+            service.verify_password(password, &hash).unwrap(),
+            PasswordVerification::NeedsRehash
+        );
+        assert!(
+            !service
+                .verify_password("wrongpassword", &hash)
+                .unwrap()
+                .is_valid()
+        );
+    }
+
+    #[test]
+    fn test_legacy_salt_hash_without_legacy_salt_configured() {
+        let service = HashService::new();
+        let password = "legacy_password";
+        let hash = legacy_hash(password);
+
+        assert_eq!(
+            service.verify_password(password, &hash).unwrap(),
+            PasswordVerification::Valid
+        );
+    }
+
+    #[test]
+    fn test_hash_uses_configured_params() {
+        let service = HashService::with_config(cheap_config()).unwrap();
+        let hash = service.hash_password("password123").unwrap();
+
+        assert!(hash.starts_with("$argon2i$v=19$m=1024,t=1,p=1$"));
+        assert!(!service.needs_rehash(&hash));
+    }
+
+    #[test]
+    fn test_needs_rehash_after_config_change() {
+        let old_service = HashService::with_config(cheap_config()).unwrap();
+        let new_service = HashService::with_config(cheap_config().with_time_cost(2)).unwrap();
+        let password = "password123";
+        let hash = old_service.hash_password(password).unwrap();
+
+        assert!(new_service.needs_rehash(&hash));
+        assert_eq!(
+            new_service.verify_password(password, &hash).unwrap(),
+            PasswordVerification::NeedsRehash
+        );
+    }
+
+    #[test]
+    fn test_needs_rehash_on_algorithm_change() {
+        let old_service = HashService::with_config(cheap_config()).unwrap();
+        let new_service =
+            HashService::with_config(cheap_config().with_algorithm(HashAlgorithm::Argon2id))
+                .unwrap();
+        let hash = old_service.hash_password("password123").unwrap();
+
+        assert!(new_service.needs_rehash(&hash));
+    }
+
+    #[test]
+    fn test_needs_rehash_for_legacy_and_invalid_hashes() {
+        let service = get_hash_service();
+
+        assert!(service.needs_rehash(&legacy_hash("legacy_password")));
+        assert!(service.needs_rehash("invalid_hash"));
+    }
+
+    #[test]
+    fn test_invalid_config_is_rejected() {
+        let result = HashService::with_config(cheap_config().with_parallelism(0));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_zero_concurrent_hashes_is_rejected() {
+        let result = HashService::with_config(cheap_config().with_max_concurrent_hashes(0));
+        assert!(result.is_err());
+
+        let config: HashServiceConfig =
+            serde_json::from_str(r#"{ "max_concurrent_hashes": 0 }"#).unwrap();
+        assert!(HashService::with_config(config).is_err());
+    }
+
+    #[test]
+    fn test_peppered_hash_records_pepper_id() {
+        let service = peppered_service(PepperKeyring::new("p1", "pepper one"));
+        let password = "password123";
+        let hash = service.hash_password(password).unwrap();
+
+        assert!(hash.contains(",keyid="));
+        assert_eq!(
+            service.verify_password(password, &hash).unwrap(),
+            PasswordVerification::Valid
+        );
+        assert!(
+            !service
+                .verify_password("wrongpassword", &hash)
+                .unwrap()
+                .is_valid()
+        );
+    }
+
+    #[test]
+    fn test_pepper_is_mixed_into_hash() {
+        let service = peppered_service(PepperKeyring::new("p1", "pepper one"));
+        let other = peppered_service(PepperKeyring::new("p1", "another pepper"));
+        let password = "password123";
+        let hash = service.hash_password(password).unwrap();
+
+        assert!(!other.verify_password(password, &hash).unwrap().is_valid());
+    }
+
+    #[test]
+    fn test_pepper_rotation() {
+        let old_service = peppered_service(PepperKeyring::new("p1", "pepper one"));
+        let rotating_service = peppered_service(
+            PepperKeyring::new("p2", "pepper two").with_pepper("p1", "pepper one"),
+        );
+        let retired_service = peppered_service(
+            PepperKeyring::new("p2", "pepper two").with_retired_pepper("p1", "pepper one"),
+        );
+        let password = "password123";
+        let hash = old_service.hash_password(password).unwrap();
+
+        assert_eq!(
+            rotating_service.verify_password(password, &hash).unwrap(),
+            PasswordVerification::Valid
+        );
+        assert_eq!(
+            retired_service.verify_password(password, &hash).unwrap(),
+            PasswordVerification::NeedsRehash
+        );
+        assert!(!retired_service.needs_rehash(&retired_service.hash_password(password).unwrap()));
+    }
+
+    #[test]
+    fn test_unknown_pepper_returns_error() {
+        let old_service = peppered_service(PepperKeyring::new("p1", "pepper one"));
+        let new_service = peppered_service(PepperKeyring::new("p2", "pepper two"));
+        let hash = old_service.hash_password("password123").unwrap();
+
+        assert!(new_service.verify_password("password123", &hash).is_err());
+        assert!(new_service.needs_rehash(&hash));
+    }
+
+    #[test]
+    fn test_unpeppered_hash_needs_rehash_once_pepper_is_added() {
+        let old_service = HashService::with_config(cheap_config()).unwrap();
+        let new_service = peppered_service(PepperKeyring::new("p1", "pepper one"));
+        let password = "password123";
+        let hash = old_service.hash_password(password).unwrap();
+
+        assert_eq!(
+            new_service.verify_password(password, &hash).unwrap(),
+            PasswordVerification::NeedsRehash
+        );
+    }
+
+    #[test]
+    fn test_invalid_pepper_keyring_is_rejected() {
+        let too_long = cheap_config().with_peppers(PepperKeyring::new("too-long-id", "pepper"));
+        let retired_current = cheap_config().with_peppers(PepperKeyring {
+            current: Some("p1".to_string()),
+            ..PepperKeyring::default().with_retired_pepper("p1", "pepper")
+        });
+
+        assert!(HashService::with_config(too_long).is_err());
+        assert!(HashService::with_config(retired_current).is_err());
+    }
+
+    #[test]
+    fn test_calibrate() {
+        let config = HashService::calibrate(Duration::from_millis(20), 2048).unwrap();
+
+        assert!(config.memory_cost <= 2048);
+        assert!(config.time_cost >= 1);
+        assert!(HashService::with_config(config).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_async_hash_and_verify() {
+        let service = HashService::with_config(cheap_config()).unwrap();
+        let password = "password123";
+        let hash = service.hash_password_async(password).await.unwrap();
+
+        assert_eq!(
+            service
+                .verify_password_async(password, &hash)
+                .await
+                .unwrap(),
+            PasswordVerification::Valid
+        );
+        assert_eq!(
+            service
+                .verify_password_async("wrongpassword", &hash)
+                .await
+                .unwrap(),
+            PasswordVerification::Invalid
+        );
+    }
+
+    #[tokio::test]
+    async fn test_async_rejects_when_saturated() {
+        let service =
+            HashService::with_config(cheap_config().with_max_concurrent_hashes(1)).unwrap();
+        let permit = service.semaphore.clone().try_acquire_owned().unwrap();
+
+        assert!(matches!(
+            service.hash_password_async("password123").await,
+            Err(AuthError::TooManyRequests)
+        ));
+
+        drop(permit);
+        assert!(service.hash_password_async("password123").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_async_verify_invalid_hash_returns_error() {
+        let service = get_hash_service();
+        let result = service
+            .verify_password_async("password", "invalid_hash")
+            .await;
+        assert!(matches!(result, Err(AuthError::InternalError)));
     }
 
     #[test]
@@ -87,7 +625,26 @@ mod tests {
         assert!(result.is_err());
     }
 
+    fn legacy_hash(password: &str) -> String {
+        let salt = SaltString::encode_b64(SALT.as_bytes()).unwrap();
+        Argon2::default()
+            .hash_password(password.as_bytes(), &salt)
+            .unwrap()
+            .to_string()
+    }
+
+    fn cheap_config() -> HashServiceConfig {
+        HashServiceConfig::default()
+            .with_algorithm(HashAlgorithm::Argon2i)
+            .with_memory_cost(1024)
+            .with_time_cost(1)
+    }
+
+    fn peppered_service(peppers: PepperKeyring) -> HashService {
+        HashService::with_config(cheap_config().with_peppers(peppers)).unwrap()
+    }
+
     fn get_hash_service() -> HashService {
-        HashService::new(SALT)
+        HashService::with_legacy_salt(SALT)
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,245 @@
+use crate::auth::error::AuthError;
+use aes_kw::KekAes256;
+use argon2::password_hash::rand_core::OsRng;
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use p256::ecdh::EphemeralSecret;
+use p256::elliptic_curve::sec1::{FromEncodedPoint, ToEncodedPoint};
+use p256::pkcs8::{DecodePrivateKey, DecodePublicKey};
+use p256::{EncodedPoint, FieldBytes};
+use rsa::pkcs1::{DecodeRsaPrivateKey, DecodeRsaPublicKey};
+use rsa::{Oaep, RsaPrivateKey, RsaPublicKey};
+use serde::{Deserialize, Serialize};
+
+/// The key management algorithm (RFC 7518) a [`JweKey`] wraps content keys with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JweAlgorithm {
+    /// `RSA-OAEP`, with SHA-1. Prefer `RSA-OAEP-256` unless a client needs this one.
+    RsaOaep,
+    /// `RSA-OAEP-256`, with SHA-256.
+    RsaOaep256,
+    /// `ECDH-ES+A256KW`, an ephemeral P-256 key agreement whose result wraps the
+    /// content key with AES key wrap.
+    EcdhEsA256Kw,
+}
+
+impl JweAlgorithm {
+    /// The `alg` header value.
+    pub fn name(&self) -> &'static str {
+        match self {
+            JweAlgorithm::RsaOaep => "RSA-OAEP",
+            JweAlgorithm::RsaOaep256 => "RSA-OAEP-256",
+            JweAlgorithm::EcdhEsA256Kw => "ECDH-ES+A256KW",
+        }
+    }
+}
+
+/// A key used by [`JweService`](super::jwe_service::JweService) to wrap the content
+/// key of every token, pinned to one [`JweAlgorithm`].
+///
+/// Keys are given as PEM and parsed when the key is built. The public key encrypts;
+/// without the private key the key can't decrypt, which is all an issuer encrypting
+/// tokens for another service needs.
+///
+/// # Example
+/// ```
+/// use lunna_actix_utils::auth::service::jwe_key::{JweAlgorithm, JweKey};
+///
+/// assert!(JweKey::rsa(JweAlgorithm::EcdhEsA256Kw, None, "not a key".to_string()).is_err());
+/// ```
+#[derive(Clone)]
+pub struct JweKey {
+    algorithm: JweAlgorithm,
+    material: KeyMaterial,
+}
+
+#[derive(Clone)]
+enum KeyMaterial {
+    Rsa {
+        public: Box<RsaPublicKey>,
+        private: Option<Box<RsaPrivateKey>>,
+    },
+    P256 {
+        public: p256::PublicKey,
+        private: Option<p256::SecretKey>,
+    },
+}
+
+/// The ephemeral public key sent in the `epk` header of `ECDH-ES` tokens.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct EphemeralKey {
+    kty: String,
+    crv: String,
+    x: String,
+    y: String,
+}
+
+impl JweKey {
+    /// An RSA key for `RSA-OAEP` or `RSA-OAEP-256`, in PKCS#8/SPKI or PKCS#1 PEM.
+    pub fn rsa(
+        algorithm: JweAlgorithm,
+        private_key: Option<String>,
+        public_key: String,
+    ) -> Result<JweKey, AuthError> {
+        if algorithm == JweAlgorithm::EcdhEsA256Kw {
+            return Err(AuthError::InvalidKey);
+        }
+
+        let public = RsaPublicKey::from_public_key_pem(&public_key)
+            .or_else(|_| RsaPublicKey::from_pkcs1_pem(&public_key))
+            .map_err(|_| AuthError::InvalidKey)?;
+        let private = private_key
+            .map(|private_key| {
+                RsaPrivateKey::from_pkcs8_pem(&private_key)
+                    .or_else(|_| RsaPrivateKey::from_pkcs1_pem(&private_key))
+                    .map_err(|_| AuthError::InvalidKey)
+            })
+            .transpose()?;
+
+        Ok(JweKey {
+            algorithm,
+            material: KeyMaterial::Rsa {
+                public: Box::new(public),
+                private: private.map(Box::new),
+            },
+        })
+    }
+
+    /// A P-256 key for `ECDH-ES+A256KW`, in PKCS#8/SPKI or SEC1 PEM.
+    pub fn ec(private_key: Option<String>, public_key: String) -> Result<JweKey, AuthError> {
+        let public =
+            p256::PublicKey::from_public_key_pem(&public_key).map_err(|_| AuthError::InvalidKey)?;
+        let private = private_key
+            .map(|private_key| {
+                p256::SecretKey::from_pkcs8_pem(&private_key)
+                    .or_else(|_| p256::SecretKey::from_sec1_pem(&private_key))
+                    .map_err(|_| AuthError::InvalidKey)
+            })
+            .transpose()?;
+
+        Ok(JweKey {
+            algorithm: JweAlgorithm::EcdhEsA256Kw,
+            material: KeyMaterial::P256 { public, private },
+        })
+    }
+
+    pub fn algorithm(&self) -> JweAlgorithm {
+        self.algorithm
+    }
+
+    /// Whether the key has its private half, which decrypting takes.
+    pub fn can_decrypt(&self) -> bool {
+        match &self.material {
+            KeyMaterial::Rsa { private, .. } => private.is_some(),
+            KeyMaterial::P256 { private, .. } => private.is_some(),
+        }
+    }
+
+    /// Wraps a content key, returning the JWE encrypted key and, for `ECDH-ES`, the
+    /// ephemeral key the recipient needs to unwrap it.
+    pub(crate) fn wrap(&self, cek: &[u8]) -> Result<(Vec<u8>, Option<EphemeralKey>), AuthError> {
+        match &self.material {
+            KeyMaterial::Rsa { public, .. } => {
+                let encrypted_key = public
+                    .encrypt(&mut OsRng, self.oaep(), cek)
+                    .map_err(|_| AuthError::InternalError)?;
+                Ok((encrypted_key, None))
+            }
+            KeyMaterial::P256 { public, .. } => {
+                let ephemeral = EphemeralSecret::random(&mut OsRng);
+                let shared = ephemeral.diffie_hellman(public);
+                let kek = self.derive_kek(shared.raw_secret_bytes())?;
+                let encrypted_key = kek.wrap_vec(cek).map_err(|_| AuthError::InternalError)?;
+
+                let point = ephemeral.public_key().to_encoded_point(false);
+                let (Some(x), Some(y)) = (point.x(), point.y()) else {
+                    return Err(AuthError::InternalError);
+                };
+                let epk = EphemeralKey {
+                    kty: "EC".to_string(),
+                    crv: "P-256".to_string(),
+                    x: URL_SAFE_NO_PAD.encode(x),
+                    y: URL_SAFE_NO_PAD.encode(y),
+                };
+
+                Ok((encrypted_key, Some(epk)))
+            }
+        }
+    }
+
+    /// Recovers the content key of a token.
+    pub(crate) fn unwrap(
+        &self,
+        encrypted_key: &[u8],
+        epk: Option<&EphemeralKey>,
+    ) -> Result<Vec<u8>, AuthError> {
+        match &self.material {
+            KeyMaterial::Rsa { private, .. } => private
+                .as_ref()
+                .ok_or(AuthError::NoPrivateKey)?
+                .decrypt(self.oaep(), encrypted_key)
+                .map_err(|_| AuthError::InvalidToken),
+            KeyMaterial::P256 { private, .. } => {
+                let private = private.as_ref().ok_or(AuthError::NoPrivateKey)?;
+                let epk = epk.ok_or(AuthError::InvalidToken)?;
+                let shared = p256::ecdh::diffie_hellman(
+                    private.to_nonzero_scalar(),
+                    epk.public_key()?.as_affine(),
+                );
+
+                self.derive_kek(shared.raw_secret_bytes())?
+                    .unwrap_vec(encrypted_key)
+                    .map_err(|_| AuthError::InvalidToken)
+            }
+        }
+    }
+
+    fn oaep(&self) -> Oaep {
+        match self.algorithm {
+            JweAlgorithm::RsaOaep => Oaep::new::<sha1::Sha1>(),
+            _ => Oaep::new::<sha2::Sha256>(),
+        }
+    }
+
+    /// The Concat KDF of RFC 7518 section 4.6.2, without `apu` and `apv`.
+    fn derive_kek(&self, shared_secret: &[u8]) -> Result<KekAes256, AuthError> {
+        let algorithm = self.algorithm.name().as_bytes();
+
+        let mut other_info = Vec::new();
+        other_info.extend_from_slice(&(algorithm.len() as u32).to_be_bytes());
+        other_info.extend_from_slice(algorithm);
+        other_info.extend_from_slice(&0u32.to_be_bytes());
+        other_info.extend_from_slice(&0u32.to_be_bytes());
+        other_info.extend_from_slice(&256u32.to_be_bytes());
+
+        let mut kek = [0u8; 32];
+        concat_kdf::derive_key_into::<sha2::Sha256>(shared_secret, &other_info, &mut kek)
+            .map_err(|_| AuthError::InternalError)?;
+
+        Ok(KekAes256::from(kek))
+    }
+}
+
+impl EphemeralKey {
+    fn public_key(&self) -> Result<p256::PublicKey, AuthError> {
+        if self.kty != "EC" || self.crv != "P-256" {
+            return Err(AuthError::InvalidToken);
+        }
+
+        let coordinate = |value: &str| {
+            URL_SAFE_NO_PAD
+                .decode(value)
+                .ok()
+                .filter(|bytes| bytes.len() == 32)
+                .map(|bytes| *FieldBytes::from_slice(&bytes))
+                .ok_or(AuthError::InvalidToken)
+        };
+        let point = EncodedPoint::from_affine_coordinates(
+            &coordinate(&self.x)?,
+            &coordinate(&self.y)?,
+            false,
+        );
+
+        Option::from(p256::PublicKey::from_encoded_point(&point)).ok_or(AuthError::InvalidToken)
+    }
+}
@@ -0,0 +1,399 @@
+use crate::auth::error::AuthError;
+use crate::auth::service::jwe_key::{EphemeralKey, JweKey};
+use crate::auth::service::jwt_service::JwtService;
+use crate::auth::service::token_claims::JwtDataContainer;
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+/// The only content encryption algorithm supported.
+const ENCRYPTION: &str = "A256GCM";
+
+/// The length of an AES-GCM tag.
+const TAG_LENGTH: usize = 16;
+
+#[derive(Serialize, Deserialize)]
+struct JweHeader {
+    alg: String,
+    enc: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    cty: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    kid: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    epk: Option<EphemeralKey>,
+}
+
+/// Issues and checks encrypted tokens (JWE, RFC 7516), for claims that intermediaries
+/// must not be able to read.
+///
+/// Tokens are signed by the wrapped [`JwtService`] first, then the signed token is
+/// encrypted with `A256GCM` under a random content key, wrapped with the [`JweKey`].
+/// The result is a nested JWT in JWE compact serialization, with `cty: JWT`.
+///
+/// Decrypting and verifying happen in one call, which returns the same
+/// [`JwtDataContainer`] as [`JwtService::verify_token`]: expiry, validation rules,
+/// claims layout and, with [`JweService::verify_token_async`], revocation all come from
+/// the [`JwtService`].
+///
+/// # Example
+/// ```
+/// use jsonwebtoken::Algorithm;
+/// use lunna_actix_utils::auth::service::jwe_key::{JweAlgorithm, JweKey};
+/// use lunna_actix_utils::auth::service::jwe_service::JweService;
+/// use lunna_actix_utils::auth::service::jwt_key::JwtKey;
+/// use lunna_actix_utils::auth::service::jwt_service::{JwtService, get_current_time};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let private_pem =
+/// #     include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/rsa_private.pem"));
+/// # let public_pem =
+/// #     include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/rsa_public.pem"));
+/// let jwt_service = JwtService::with_key(JwtKey::hmac(Algorithm::HS256, b"signing secret")?);
+/// let key = JweKey::rsa(
+///     JweAlgorithm::RsaOaep256,
+///     Some(private_pem.to_string()),
+///     public_pem.to_string(),
+/// )?;
+/// let jwe_service = JweService::new(jwt_service, key);
+///
+/// let token = jwe_service.generate_token("lunna@lunna.dev".to_string(), get_current_time() + 60)?;
+/// assert_eq!(token.split('.').count(), 5);
+///
+/// let claims = jwe_service.verify_token::<String>(&token)?;
+/// assert_eq!(claims.data, "lunna@lunna.dev");
+/// # Ok(())
+/// # }
+/// ```
+pub struct JweService {
+    jwt_service: JwtService,
+    key: JweKey,
+    kid: Option<String>,
+}
+
+impl JweService {
+    pub fn new(jwt_service: JwtService, key: JweKey) -> JweService {
+        JweService {
+            jwt_service,
+            key,
+            kid: None,
+        }
+    }
+
+    /// Sets the `kid` sent in the JWE header. Tokens naming another `kid` are rejected.
+    pub fn with_key_id(mut self, kid: &str) -> Self {
+        self.kid = Some(kid.to_string());
+        self
+    }
+
+    /// The service that signs and verifies the inner tokens.
+    pub fn jwt_service(&self) -> &JwtService {
+        &self.jwt_service
+    }
+
+    pub fn generate_token<T>(&self, data: T, until: u64) -> Result<String, AuthError>
+    where
+        T: Serialize + DeserializeOwned,
+    {
+        self.encrypt(&self.jwt_service.generate_token(data, until)?)
+    }
+
+    /// Signs then encrypts a container built with [`JwtDataContainer::new`].
+    pub fn generate_token_with_data_container<T>(
+        &self,
+        data: JwtDataContainer<T>,
+    ) -> Result<String, AuthError>
+    where
+        T: Serialize + DeserializeOwned,
+    {
+        self.encrypt(&self.jwt_service.generate_token_with_data_container(data)?)
+    }
+
+    /// Decrypts a token, then verifies the signed token inside it like
+    /// [`JwtService::verify_token`], which fails if the [`JwtService`] has a
+    /// revocation store.
+    pub fn verify_token<T>(&self, token: &str) -> Result<JwtDataContainer<T>, AuthError>
+    where
+        T: Serialize + DeserializeOwned + Send + Sync,
+    {
+        self.jwt_service.verify_token(&self.decrypt(token)?)
+    }
+
+    /// Decrypts a token, then verifies the signed token inside it like
+    /// [`JwtService::verify_token_async`], revocation included.
+    pub async fn verify_token_async<T>(&self, token: &str) -> Result<JwtDataContainer<T>, AuthError>
+    where
+        T: Serialize + DeserializeOwned + Send + Sync,
+    {
+        self.jwt_service
+            .verify_token_async(&self.decrypt(token)?)
+            .await
+    }
+
+    /// Encrypts an already signed token.
+    pub fn encrypt(&self, signed_token: &str) -> Result<String, AuthError> {
+        let mut cek = [0u8; 32];
+        OsRng.fill_bytes(&mut cek);
+        let mut iv = [0u8; 12];
+        OsRng.fill_bytes(&mut iv);
+
+        let (encrypted_key, epk) = self.key.wrap(&cek)?;
+        let header = JweHeader {
+            alg: self.key.algorithm().name().to_string(),
+            enc: ENCRYPTION.to_string(),
+            cty: Some("JWT".to_string()),
+            kid: self.kid.clone(),
+            epk,
+        };
+        let header = URL_SAFE_NO_PAD
+            .encode(serde_json::to_vec(&header).map_err(|_| AuthError::InternalError)?);
+
+        let mut ciphertext = Aes256Gcm::new(&cek.into())
+            .encrypt(
+                Nonce::from_slice(&iv),
+                Payload {
+                    msg: signed_token.as_bytes(),
+                    aad: header.as_bytes(),
+                },
+            )
+            .map_err(|_| AuthError::InternalError)?;
+        let tag = ciphertext.split_off(ciphertext.len() - TAG_LENGTH);
+
+        Ok([
+            header,
+            URL_SAFE_NO_PAD.encode(encrypted_key),
+            URL_SAFE_NO_PAD.encode(iv),
+            URL_SAFE_NO_PAD.encode(ciphertext),
+            URL_SAFE_NO_PAD.encode(tag),
+        ]
+        .join("."))
+    }
+
+    /// Decrypts a token, returning the signed token inside it unverified.
+    pub fn decrypt(&self, token: &str) -> Result<String, AuthError> {
+        let [header, encrypted_key, iv, ciphertext, tag] = token
+            .split('.')
+            .collect::<Vec<_>>()
+            .try_into()
+            .map_err(|_| AuthError::InvalidToken)?;
+        let decode = |part: &str| {
+            URL_SAFE_NO_PAD
+                .decode(part)
+                .map_err(|_| AuthError::InvalidToken)
+        };
+
+        let parsed: JweHeader =
+            serde_json::from_slice(&decode(header)?).map_err(|_| AuthError::InvalidToken)?;
+
+        // Like signing keys, the key is pinned to its algorithm: the header can't pick
+        // another one.
+        if parsed.alg != self.key.algorithm().name() || parsed.enc != ENCRYPTION {
+            return Err(AuthError::InvalidToken);
+        }
+        if let (Some(kid), Some(expected)) = (&parsed.kid, &self.kid)
+            && kid != expected
+        {
+            return Err(AuthError::InvalidToken);
+        }
+
+        let cek = self
+            .key
+            .unwrap(&decode(encrypted_key)?, parsed.epk.as_ref())?;
+        let iv = decode(iv)?;
+        if cek.len() != 32 || iv.len() != 12 {
+            return Err(AuthError::InvalidToken);
+        }
+
+        let mut ciphertext = decode(ciphertext)?;
+        ciphertext.extend_from_slice(&decode(tag)?);
+
+        let signed_token = Aes256Gcm::new_from_slice(&cek)
+            .map_err(|_| AuthError::InvalidToken)?
+            .decrypt(
+                Nonce::from_slice(&iv),
+                Payload {
+                    msg: &ciphertext,
+                    aad: header.as_bytes(),
+                },
+            )
+            .map_err(|_| AuthError::InvalidToken)?;
+
+        String::from_utf8(signed_token).map_err(|_| AuthError::InvalidToken)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::service::clock::{Clock, MockClock};
+    use crate::auth::service::jwe_key::JweAlgorithm;
+    use crate::auth::service::jwt_key::JwtKey;
+    use crate::auth::service::revocation_store::InMemoryRevocationStore;
+    use crate::auth::test_keys::*;
+    use jsonwebtoken::Algorithm;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    fn jwt_service(clock: Arc<MockClock>) -> JwtService {
+        JwtService::with_key(JwtKey::hmac(Algorithm::HS256, HMAC_TEST_SECRET).unwrap())
+            .with_clock(clock)
+    }
+
+    fn keys() -> Vec<JweKey> {
+        vec![
+            JweKey::rsa(
+                JweAlgorithm::RsaOaep,
+                Some(RSA_PRIVATE_TEST_KEY.to_string()),
+                RSA_PUBLIC_TEST_KEY.to_string(),
+            )
+            .unwrap(),
+            JweKey::rsa(
+                JweAlgorithm::RsaOaep256,
+                Some(RSA_PRIVATE_TEST_KEY.to_string()),
+                RSA_PUBLIC_TEST_KEY.to_string(),
+            )
+            .unwrap(),
+            JweKey::ec(
+                Some(EC_PRIVATE_TEST_KEY.to_string()),
+                EC_PUBLIC_TEST_KEY.to_string(),
+            )
+            .unwrap(),
+        ]
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let clock = Arc::new(MockClock::new(1_700_000_000));
+
+        for key in keys() {
+            let service = JweService::new(jwt_service(clock.clone()), key).with_key_id("enc-1");
+            let token = service
+                .generate_token("lunna@lunna.dev".to_string(), clock.now() + 60)
+                .unwrap();
+
+            let header: serde_json::Value = serde_json::from_slice(
+                &URL_SAFE_NO_PAD
+                    .decode(token.split('.').next().unwrap())
+                    .unwrap(),
+            )
+            .unwrap();
+            assert_eq!(header["enc"], "A256GCM");
+            assert_eq!(header["cty"], "JWT");
+            assert_eq!(header["kid"], "enc-1");
+
+            let claims = service.verify_token::<String>(&token).unwrap();
+            assert_eq!(claims.data, "lunna@lunna.dev");
+
+            // The inner token is a regular signed one.
+            let signed_token = service.decrypt(&token).unwrap();
+            assert!(
+                service
+                    .jwt_service()
+                    .verify_token::<String>(&signed_token)
+                    .is_ok()
+            );
+        }
+    }
+
+    #[test]
+    fn test_tampered_token() {
+        let clock = Arc::new(MockClock::new(1_700_000_000));
+
+        for key in keys() {
+            let service = JweService::new(jwt_service(clock.clone()), key);
+            let token = service
+                .generate_token("lunna@lunna.dev".to_string(), clock.now() + 60)
+                .unwrap();
+            let mut parts: Vec<String> = token.split('.').map(str::to_string).collect();
+
+            let mut ciphertext = URL_SAFE_NO_PAD.decode(&parts[3]).unwrap();
+            ciphertext[0] ^= 1;
+            parts[3] = URL_SAFE_NO_PAD.encode(ciphertext);
+
+            assert!(matches!(
+                service.verify_token::<String>(&parts.join(".")),
+                Err(AuthError::InvalidToken)
+            ));
+            assert!(matches!(
+                service.verify_token::<String>("a.b.c"),
+                Err(AuthError::InvalidToken)
+            ));
+        }
+    }
+
+    #[test]
+    fn test_algorithm_is_pinned() {
+        let clock = Arc::new(MockClock::new(1_700_000_000));
+        let mut keys = keys();
+        let rsa_oaep_256 = keys.remove(1);
+        let rsa_oaep = keys.remove(0);
+
+        let issuer = JweService::new(jwt_service(clock.clone()), rsa_oaep);
+        let verifier = JweService::new(jwt_service(clock.clone()), rsa_oaep_256);
+        let token = issuer
+            .generate_token("lunna".to_string(), clock.now() + 60)
+            .unwrap();
+
+        assert!(matches!(
+            verifier.verify_token::<String>(&token),
+            Err(AuthError::InvalidToken)
+        ));
+    }
+
+    #[test]
+    fn test_encrypt_only_key() {
+        let clock = Arc::new(MockClock::new(1_700_000_000));
+        let key = JweKey::ec(None, EC_PUBLIC_TEST_KEY.to_string()).unwrap();
+        assert!(!key.can_decrypt());
+
+        let service = JweService::new(jwt_service(clock.clone()), key);
+        let token = service
+            .generate_token("lunna".to_string(), clock.now() + 60)
+            .unwrap();
+
+        assert!(matches!(
+            service.verify_token::<String>(&token),
+            Err(AuthError::NoPrivateKey)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_inner_token_is_verified() {
+        let clock = Arc::new(MockClock::new(1_700_000_000));
+        let store = Arc::new(InMemoryRevocationStore::new().with_clock(clock.clone()));
+        let jwt_service = jwt_service(clock.clone()).with_revocation_store(store);
+        let key = keys().remove(0);
+        let service = JweService::new(jwt_service, key);
+
+        let token = service
+            .generate_token("lunna".to_string(), clock.now() + 60)
+            .unwrap();
+        service
+            .jwt_service()
+            .revoke_token(&service.decrypt(&token).unwrap())
+            .await
+            .unwrap();
+        assert!(matches!(
+            service.verify_token_async::<String>(&token).await,
+            Err(AuthError::TokenRevoked)
+        ));
+
+        let token = service
+            .generate_token("lunna".to_string(), clock.now() + 60)
+            .unwrap();
+        assert!(matches!(
+            service.verify_token::<String>(&token),
+            Err(AuthError::RevocationCheckRequired)
+        ));
+
+        clock.advance(Duration::from_secs(61));
+        assert!(matches!(
+            service.verify_token_async::<String>(&token).await,
+            Err(AuthError::TokenExpired)
+        ));
+    }
+}
@@ -0,0 +1,439 @@
+use crate::auth::error::AuthError;
+use crate::auth::service::clock::{Clock, SystemClock};
+use crate::auth::service::jwt_key::JwtKey;
+use crate::auth::service::jwt_service::verify_token_with_key;
+use crate::auth::service::jwt_validation_config::JwtValidationConfig;
+use crate::auth::service::token_claims::{ClaimsLayout, JwtDataContainer};
+use jsonwebtoken::decode_header;
+use jsonwebtoken::jwk::JwkSet;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+/// Where a [`JwksVerifier`] loads its keys from.
+#[derive(Debug, Clone)]
+pub enum JwksSource {
+    /// A JWKS endpoint, such as the one served by
+    /// [`jwks_scope`](crate::auth::handler::jwks_handler::jwks_scope).
+    Url(String),
+    /// A JWKS document on disk.
+    File(PathBuf),
+}
+
+/// Verifies tokens with keys loaded from a JWKS document instead of a configured PEM.
+///
+/// This is the counterpart to [`JwtService::new_without_private`](super::jwt_service::JwtService::new_without_private)
+/// for services that only check tokens issued elsewhere. Keys are cached for the
+/// `max-age` of the `Cache-Control` header sent with the document, or for the default
+/// TTL when there is none. A token whose `kid` isn't cached triggers a refetch, so keys
+/// added by the issuer are picked up right away. Refetches are at most one per
+/// [`JwksVerifier::with_min_refresh_interval`], so a flood of made-up `kid`s can't be
+/// turned into a flood of requests to the issuer. If a refetch fails, the keys already
+/// loaded keep being used.
+///
+/// Requires the `jwks` feature.
+///
+/// # Example
+/// ```no_run
+/// use lunna_actix_utils::auth::service::jwks_verifier::{JwksSource, JwksVerifier};
+///
+/// # async fn example(token: &str) {
+/// let verifier = JwksVerifier::new(JwksSource::Url(
+///     "https://auth.example.com/.well-known/jwks.json".to_string(),
+/// ));
+///
+/// let claims = verifier.verify_token::<String>(token).await;
+/// # }
+/// ```
+pub struct JwksVerifier {
+    source: JwksSource,
+    default_ttl: Duration,
+    min_refresh_interval: Duration,
+    timeout: Duration,
+    validation: JwtValidationConfig,
+    clock: Arc<dyn Clock>,
+    layout: ClaimsLayout,
+    cache: RwLock<CachedKeys>,
+    last_fetch: Mutex<Option<Instant>>,
+}
+
+struct CachedKeys {
+    keys: Vec<(String, Arc<JwtKey>)>,
+    expires_at: Option<Instant>,
+}
+
+impl JwksVerifier {
+    pub fn new(source: JwksSource) -> JwksVerifier {
+        JwksVerifier {
+            source,
+            default_ttl: Duration::from_secs(300),
+            min_refresh_interval: Duration::from_secs(10),
+            timeout: Duration::from_secs(5),
+            validation: JwtValidationConfig::default(),
+            clock: Arc::new(SystemClock),
+            layout: ClaimsLayout::default(),
+            cache: RwLock::new(CachedKeys {
+                keys: Vec::new(),
+                expires_at: None,
+            }),
+            last_fetch: Mutex::new(None),
+        }
+    }
+
+    /// How long keys are cached when the source doesn't say. Defaults to 5 minutes.
+    pub fn with_default_ttl(mut self, default_ttl: Duration) -> Self {
+        self.default_ttl = default_ttl;
+        self
+    }
+
+    /// The shortest time between two fetches. Defaults to 10 seconds.
+    pub fn with_min_refresh_interval(mut self, min_refresh_interval: Duration) -> Self {
+        self.min_refresh_interval = min_refresh_interval;
+        self
+    }
+
+    /// How long a fetch from a URL may take. Defaults to 5 seconds.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets how the registered claims of incoming tokens are checked.
+    pub fn with_validation(mut self, validation: JwtValidationConfig) -> Self {
+        self.validation = validation;
+        self
+    }
+
+    /// Sets the clock used for checking `exp` and `nbf`.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Sets where the payload is read from. Tokens from third-party issuers are
+    /// usually [`ClaimsLayout::Flat`].
+    pub fn with_claims_layout(mut self, layout: ClaimsLayout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    /// Verifies a token with the key its `kid` names.
+    ///
+    /// A token without a `kid` is only accepted when the document has a single key.
+    pub async fn verify_token<T>(&self, token: &str) -> Result<JwtDataContainer<T>, AuthError>
+    where
+        T: Serialize + DeserializeOwned + Send + Sync,
+    {
+        let kid = decode_header(token)
+            .map_err(|_| AuthError::InvalidToken)?
+            .kid;
+
+        let key = match self.cached_key(kid.as_deref(), false) {
+            Some(key) => key,
+            None => {
+                self.refresh_if_allowed().await;
+                self.cached_key(kid.as_deref(), true)
+                    .ok_or(AuthError::InvalidToken)?
+            }
+        };
+
+        verify_token_with_key(token, &key, &self.validation, self.layout, self.clock.now())
+    }
+
+    /// Fetches the keys now, ignoring the cache and the refresh interval.
+    ///
+    /// Useful at startup to fail fast on a wrong URL. Fails with
+    /// [`AuthError::InternalError`] if the source can't be read and with
+    /// [`AuthError::InvalidKey`] if it isn't a JWKS document.
+    pub async fn refresh(&self) -> Result<(), AuthError> {
+        let mut last_fetch = self.last_fetch.lock().await;
+        *last_fetch = Some(Instant::now());
+
+        self.fetch_and_store().await
+    }
+
+    async fn refresh_if_allowed(&self) {
+        let mut last_fetch = self.last_fetch.lock().await;
+
+        // Whoever held the lock before us may have just fetched.
+        if let Some(at) = *last_fetch
+            && at.elapsed() < self.min_refresh_interval
+        {
+            return;
+        }
+        *last_fetch = Some(Instant::now());
+
+        // On failure the previous keys are kept until the next allowed refetch.
+        let _ = self.fetch_and_store().await;
+    }
+
+    fn cached_key(&self, kid: Option<&str>, allow_stale: bool) -> Option<Arc<JwtKey>> {
+        let cache = self.cache.read().unwrap_or_else(|p| p.into_inner());
+
+        let fresh = cache.expires_at.is_some_and(|at| Instant::now() < at);
+        if !fresh && !allow_stale {
+            return None;
+        }
+
+        match kid {
+            Some(kid) => cache
+                .keys
+                .iter()
+                .find(|(key_id, _)| key_id == kid)
+                .map(|(_, key)| key.clone()),
+            None => match cache.keys.as_slice() {
+                [(_, key)] => Some(key.clone()),
+                _ => None,
+            },
+        }
+    }
+
+    async fn fetch_and_store(&self) -> Result<(), AuthError> {
+        let (document, ttl) = self.fetch().await?;
+        let set: JwkSet = serde_json::from_str(&document).map_err(|_| AuthError::InvalidKey)?;
+
+        // Keys we can't use, like encryption keys, are skipped instead of failing the set.
+        let keys = set
+            .keys
+            .iter()
+            .filter_map(|jwk| {
+                let kid = jwk.common.key_id.clone().unwrap_or_default();
+                JwtKey::from_jwk(jwk).ok().map(|key| (kid, Arc::new(key)))
+            })
+            .collect();
+
+        let mut cache = self.cache.write().unwrap_or_else(|p| p.into_inner());
+        *cache = CachedKeys {
+            keys,
+            expires_at: Some(Instant::now() + ttl.unwrap_or(self.default_ttl)),
+        };
+
+        Ok(())
+    }
+
+    /// Reads the document and, for URLs, the TTL from its `Cache-Control` header.
+    async fn fetch(&self) -> Result<(String, Option<Duration>), AuthError> {
+        match &self.source {
+            JwksSource::File(path) => tokio::fs::read_to_string(path)
+                .await
+                .map(|document| (document, None))
+                .map_err(|_| AuthError::InternalError),
+            JwksSource::Url(url) => {
+                let url = url.clone();
+                let timeout = self.timeout;
+
+                tokio::task::spawn_blocking(move || {
+                    let response = ureq::get(&url)
+                        .timeout(timeout)
+                        .call()
+                        .map_err(|_| AuthError::InternalError)?;
+                    let ttl = response.header("Cache-Control").and_then(max_age);
+                    let document = response
+                        .into_string()
+                        .map_err(|_| AuthError::InternalError)?;
+
+                    Ok((document, ttl))
+                })
+                .await
+                .map_err(|_| AuthError::InternalError)?
+            }
+        }
+    }
+}
+
+/// Reads the cache lifetime out of a `Cache-Control` header.
+fn max_age(cache_control: &str) -> Option<Duration> {
+    let directives = cache_control.split(',').map(str::trim);
+
+    let mut max_age = None;
+    for directive in directives {
+        if directive.eq_ignore_ascii_case("no-cache") || directive.eq_ignore_ascii_case("no-store")
+        {
+            return Some(Duration::ZERO);
+        }
+        if let Some((name, value)) = directive.split_once('=')
+            && name.trim().eq_ignore_ascii_case("max-age")
+        {
+            max_age = value.trim().trim_matches('"').parse().ok();
+        }
+    }
+
+    max_age.map(Duration::from_secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::service::jwt_keyring::JwtKeyring;
+    use crate::auth::service::jwt_service::{JwtService, get_current_time};
+    use crate::auth::test_keys::*;
+    use jsonwebtoken::Algorithm;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// A minimal JWKS endpoint publishing the keys of `issuer`, counting its hits.
+    fn serve_jwks(
+        issuer: Arc<JwtService>,
+        cache_control: &'static str,
+    ) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!(
+            "http://{}/.well-known/jwks.json",
+            listener.local_addr().unwrap()
+        );
+        let hits = Arc::new(AtomicUsize::new(0));
+        let counter = hits.clone();
+
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut request = Vec::new();
+                let mut buffer = [0u8; 1024];
+                while !request.ends_with(b"\r\n\r\n") {
+                    let read = stream.read(&mut buffer).unwrap();
+                    if read == 0 {
+                        break;
+                    }
+                    request.extend_from_slice(&buffer[..read]);
+                }
+
+                counter.fetch_add(1, Ordering::SeqCst);
+                let body = serde_json::to_string(&issuer.jwk_set()).unwrap();
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nCache-Control: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    cache_control,
+                    body.len(),
+                    body
+                );
+            }
+        });
+
+        (url, hits)
+    }
+
+    fn rsa_key() -> JwtKey {
+        JwtKey::rsa(
+            Algorithm::RS256,
+            Some(RSA_PRIVATE_TEST_KEY.to_string()),
+            RSA_PUBLIC_TEST_KEY.to_string(),
+        )
+        .unwrap()
+    }
+
+    fn token(issuer: &JwtService) -> String {
+        issuer
+            .generate_token("payload".to_string(), get_current_time() + 60)
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_keys_are_cached() {
+        let issuer = Arc::new(JwtService::with_keyring(JwtKeyring::new("a", rsa_key())));
+        let (url, hits) = serve_jwks(issuer.clone(), "public, max-age=300");
+        let verifier = JwksVerifier::new(JwksSource::Url(url));
+
+        for _ in 0..3 {
+            let claims = verifier
+                .verify_token::<String>(&token(&issuer))
+                .await
+                .unwrap();
+            assert_eq!(claims.data, "payload");
+        }
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_expired_cache_is_refetched() {
+        let issuer = Arc::new(JwtService::with_keyring(JwtKeyring::new("a", rsa_key())));
+        let (url, hits) = serve_jwks(issuer.clone(), "no-cache");
+        let verifier =
+            JwksVerifier::new(JwksSource::Url(url)).with_min_refresh_interval(Duration::ZERO);
+
+        verifier
+            .verify_token::<String>(&token(&issuer))
+            .await
+            .unwrap();
+        verifier
+            .verify_token::<String>(&token(&issuer))
+            .await
+            .unwrap();
+
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_unknown_kid_triggers_rate_limited_refetch() {
+        let issuer = Arc::new(JwtService::with_keyring(JwtKeyring::new("a", rsa_key())));
+        let (url, hits) = serve_jwks(issuer.clone(), "max-age=300");
+        let verifier = JwksVerifier::new(JwksSource::Url(url))
+            .with_min_refresh_interval(Duration::from_millis(200));
+
+        verifier
+            .verify_token::<String>(&token(&issuer))
+            .await
+            .unwrap();
+
+        // The issuer rotates to a key the verifier hasn't seen yet.
+        let ec_key = JwtKey::ec(
+            Algorithm::ES256,
+            Some(EC_PRIVATE_TEST_KEY.to_string()),
+            EC_PUBLIC_TEST_KEY.to_string(),
+        )
+        .unwrap();
+        issuer.keyring().add("b", ec_key).unwrap();
+        issuer.keyring().promote("b").unwrap();
+        let rotated = token(&issuer);
+
+        // Too soon after the first fetch: the unknown kid is rejected without a request.
+        assert!(matches!(
+            verifier.verify_token::<String>(&rotated).await,
+            Err(AuthError::InvalidToken)
+        ));
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        verifier.verify_token::<String>(&rotated).await.unwrap();
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+
+        // Keys that are cached don't count against the limit.
+        verifier.verify_token::<String>(&rotated).await.unwrap();
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_file_source() {
+        let issuer = JwtService::with_keyring(JwtKeyring::new("a", rsa_key()));
+        let path = std::env::temp_dir().join("lunna_actix_utils_jwks_file.json");
+        std::fs::write(&path, serde_json::to_string(&issuer.jwk_set()).unwrap()).unwrap();
+
+        let verifier = JwksVerifier::new(JwksSource::File(path.clone()));
+        verifier.refresh().await.unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(
+            verifier
+                .verify_token::<String>(&token(&issuer))
+                .await
+                .is_ok()
+        );
+        assert!(matches!(
+            JwksVerifier::new(JwksSource::File(path)).refresh().await,
+            Err(AuthError::InternalError)
+        ));
+    }
+
+    #[test]
+    fn test_max_age() {
+        assert_eq!(
+            max_age("public, max-age=300"),
+            Some(Duration::from_secs(300))
+        );
+        assert_eq!(max_age("max-age=60, no-store"), Some(Duration::ZERO));
+        assert_eq!(max_age("public"), None);
+    }
+}
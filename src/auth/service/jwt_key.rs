@@ -0,0 +1,463 @@
+use crate::auth::error::AuthError;
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use jsonwebtoken::jwk::{
+    AlgorithmParameters, CommonParameters, EllipticCurve, EllipticCurveKeyParameters, Jwk,
+    KeyAlgorithm, OctetKeyPairParameters, PublicKeyUse, RSAKeyParameters,
+};
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, crypto};
+use pkcs1::der::Decode;
+use spki::SubjectPublicKeyInfoRef;
+use std::str::FromStr;
+
+/// A key used by [`JwtService`](super::jwt_service::JwtService), pinned to one algorithm.
+///
+/// Tokens are always signed with [`JwtKey::algorithm`], and tokens whose header names
+/// any other algorithm are rejected, so a verifier can't be tricked into checking an
+/// RSA-signed token as HMAC or the other way around.
+///
+/// Asymmetric keys are given as PEM and parsed once, when the key is built, so a bad
+/// key is reported by the constructor instead of failing at request time. So is a
+/// private key that doesn't match the public key, which would sign tokens nobody can
+/// verify. Without a private key the key can only verify.
+///
+/// # Example
+/// ```
+/// use jsonwebtoken::Algorithm;
+/// use lunna_actix_utils::auth::service::jwt_key::JwtKey;
+///
+/// let key = JwtKey::hmac(Algorithm::HS256, b"internal tools secret").unwrap();
+///
+/// assert_eq!(key.algorithm(), Algorithm::HS256);
+/// assert!(JwtKey::hmac(Algorithm::RS256, b"internal tools secret").is_err());
+/// ```
+#[derive(Clone)]
+pub struct JwtKey {
+    algorithm: Algorithm,
+    encoding_key: Option<EncodingKey>,
+    decoding_key: DecodingKey,
+    public_jwk: Option<AlgorithmParameters>,
+}
+
+impl JwtKey {
+    /// An RSA key for `RS256`, `RS384`, `RS512`, `PS256`, `PS384` or `PS512`.
+    pub fn rsa(
+        algorithm: Algorithm,
+        private_key: Option<String>,
+        public_key: String,
+    ) -> Result<JwtKey, AuthError> {
+        match algorithm {
+            Algorithm::RS256
+            | Algorithm::RS384
+            | Algorithm::RS512
+            | Algorithm::PS256
+            | Algorithm::PS384
+            | Algorithm::PS512 => Self::pem(
+                algorithm,
+                private_key,
+                public_key,
+                EncodingKey::from_rsa_pem,
+                DecodingKey::from_rsa_pem,
+            ),
+            _ => Err(AuthError::InvalidKey),
+        }
+    }
+
+    /// An elliptic curve key for `ES256` (P-256) or `ES384` (P-384).
+    pub fn ec(
+        algorithm: Algorithm,
+        private_key: Option<String>,
+        public_key: String,
+    ) -> Result<JwtKey, AuthError> {
+        match algorithm {
+            Algorithm::ES256 | Algorithm::ES384 => Self::pem(
+                algorithm,
+                private_key,
+                public_key,
+                EncodingKey::from_ec_pem,
+                DecodingKey::from_ec_pem,
+            ),
+            _ => Err(AuthError::InvalidKey),
+        }
+    }
+
+    /// An Ed25519 key, used with `EdDSA`.
+    pub fn ed25519(private_key: Option<String>, public_key: String) -> Result<JwtKey, AuthError> {
+        Self::pem(
+            Algorithm::EdDSA,
+            private_key,
+            public_key,
+            EncodingKey::from_ed_pem,
+            DecodingKey::from_ed_pem,
+        )
+    }
+
+    /// A shared secret for `HS256`, `HS384` or `HS512`. It both signs and verifies.
+    pub fn hmac(algorithm: Algorithm, secret: &[u8]) -> Result<JwtKey, AuthError> {
+        match algorithm {
+            Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512 => Ok(JwtKey {
+                algorithm,
+                encoding_key: Some(EncodingKey::from_secret(secret)),
+                decoding_key: DecodingKey::from_secret(secret),
+                public_jwk: None,
+            }),
+            _ => Err(AuthError::InvalidKey),
+        }
+    }
+
+    /// A verify-only key read from a JWK, as published in a JWKS document.
+    ///
+    /// The algorithm comes from the `alg` field, or from the key type and curve when it
+    /// is missing (`RS256` for RSA). Symmetric (`oct`) keys, encryption keys and an
+    /// `alg` that doesn't fit the key type are rejected with [`AuthError::InvalidKey`].
+    pub fn from_jwk(jwk: &Jwk) -> Result<JwtKey, AuthError> {
+        if matches!(jwk.common.public_key_use, Some(ref key_use) if *key_use != PublicKeyUse::Signature)
+        {
+            return Err(AuthError::InvalidKey);
+        }
+
+        let algorithm = match jwk.common.key_algorithm {
+            Some(key_algorithm) => Algorithm::from_str(&key_algorithm.to_string())
+                .map_err(|_| AuthError::InvalidKey)?,
+            None => match &jwk.algorithm {
+                AlgorithmParameters::RSA(_) => Algorithm::RS256,
+                AlgorithmParameters::EllipticCurve(params) => match params.curve {
+                    EllipticCurve::P256 => Algorithm::ES256,
+                    EllipticCurve::P384 => Algorithm::ES384,
+                    _ => return Err(AuthError::InvalidKey),
+                },
+                AlgorithmParameters::OctetKeyPair(_) => Algorithm::EdDSA,
+                AlgorithmParameters::OctetKey(_) => return Err(AuthError::InvalidKey),
+            },
+        };
+
+        let fits = match (&jwk.algorithm, algorithm) {
+            (
+                AlgorithmParameters::RSA(_),
+                Algorithm::RS256
+                | Algorithm::RS384
+                | Algorithm::RS512
+                | Algorithm::PS256
+                | Algorithm::PS384
+                | Algorithm::PS512,
+            ) => true,
+            (AlgorithmParameters::EllipticCurve(params), Algorithm::ES256) => {
+                params.curve == EllipticCurve::P256
+            }
+            (AlgorithmParameters::EllipticCurve(params), Algorithm::ES384) => {
+                params.curve == EllipticCurve::P384
+            }
+            (AlgorithmParameters::OctetKeyPair(params), Algorithm::EdDSA) => {
+                params.curve == EllipticCurve::Ed25519
+            }
+            _ => false,
+        };
+        if !fits {
+            return Err(AuthError::InvalidKey);
+        }
+
+        Ok(JwtKey {
+            algorithm,
+            encoding_key: None,
+            decoding_key: DecodingKey::from_jwk(jwk).map_err(|_| AuthError::InvalidKey)?,
+            public_jwk: Some(jwk.algorithm.clone()),
+        })
+    }
+
+    fn pem(
+        algorithm: Algorithm,
+        private_key: Option<String>,
+        public_key: String,
+        encoding_key: fn(&[u8]) -> jsonwebtoken::errors::Result<EncodingKey>,
+        decoding_key: fn(&[u8]) -> jsonwebtoken::errors::Result<DecodingKey>,
+    ) -> Result<JwtKey, AuthError> {
+        let encoding_key = private_key
+            .map(|private_key| encoding_key(private_key.as_bytes()))
+            .transpose()
+            .map_err(|_| AuthError::InvalidKey)?;
+        let decoding_key =
+            decoding_key(public_key.as_bytes()).map_err(|_| AuthError::InvalidKey)?;
+        let public_jwk = public_jwk(algorithm, &public_key).ok_or(AuthError::InvalidKey)?;
+
+        if let Some(encoding_key) = &encoding_key
+            && !is_pair(algorithm, encoding_key, &decoding_key)
+        {
+            return Err(AuthError::InvalidKey);
+        }
+
+        Ok(JwtKey {
+            algorithm,
+            encoding_key,
+            decoding_key,
+            public_jwk: Some(public_jwk),
+        })
+    }
+
+    /// The only algorithm this key signs and verifies with.
+    pub fn algorithm(&self) -> Algorithm {
+        self.algorithm
+    }
+
+    /// Returns `true` if this key holds private material and can sign tokens.
+    pub fn can_sign(&self) -> bool {
+        self.encoding_key.is_some()
+    }
+
+    /// Returns the public half of this key as a JWK, or `None` for HMAC secrets,
+    /// which must never be published.
+    pub fn to_jwk(&self, kid: &str) -> Option<Jwk> {
+        let algorithm = self.public_jwk.clone()?;
+
+        Some(Jwk {
+            common: CommonParameters {
+                public_key_use: Some(PublicKeyUse::Signature),
+                key_algorithm: Some(key_algorithm(self.algorithm)),
+                key_id: Some(kid.to_string()),
+                ..Default::default()
+            },
+            algorithm,
+        })
+    }
+
+    pub(crate) fn encoding_key(&self) -> Option<&EncodingKey> {
+        self.encoding_key.as_ref()
+    }
+
+    pub(crate) fn decoding_key(&self) -> &DecodingKey {
+        &self.decoding_key
+    }
+}
+
+/// Reads the JWK parameters out of a PEM public key, either SPKI (`PUBLIC KEY`) or,
+/// for RSA, PKCS#1 (`RSA PUBLIC KEY`).
+fn public_jwk(algorithm: Algorithm, pem: &str) -> Option<AlgorithmParameters> {
+    let (label, der) = pkcs1::der::pem::decode_vec(pem.as_bytes()).ok()?;
+    let encode = |bytes: &[u8]| URL_SAFE_NO_PAD.encode(bytes);
+
+    let key = match label {
+        "PUBLIC KEY" => SubjectPublicKeyInfoRef::from_der(&der)
+            .ok()?
+            .subject_public_key
+            .raw_bytes(),
+        "RSA PUBLIC KEY" => &der,
+        _ => return None,
+    };
+
+    match algorithm {
+        Algorithm::RS256
+        | Algorithm::RS384
+        | Algorithm::RS512
+        | Algorithm::PS256
+        | Algorithm::PS384
+        | Algorithm::PS512 => {
+            let rsa = pkcs1::RsaPublicKey::from_der(key).ok()?;
+
+            Some(AlgorithmParameters::RSA(RSAKeyParameters {
+                n: encode(rsa.modulus.as_bytes()),
+                e: encode(rsa.public_exponent.as_bytes()),
+                ..Default::default()
+            }))
+        }
+        Algorithm::ES256 | Algorithm::ES384 => {
+            let (curve, size) = match algorithm {
+                Algorithm::ES256 => (EllipticCurve::P256, 32),
+                _ => (EllipticCurve::P384, 48),
+            };
+
+            // An uncompressed point: 0x04 followed by the X and Y coordinates.
+            match key {
+                [0x04, point @ ..] if point.len() == size * 2 && label == "PUBLIC KEY" => Some(
+                    AlgorithmParameters::EllipticCurve(EllipticCurveKeyParameters {
+                        curve,
+                        x: encode(&point[..size]),
+                        y: encode(&point[size..]),
+                        ..Default::default()
+                    }),
+                ),
+                _ => None,
+            }
+        }
+        Algorithm::EdDSA if key.len() == 32 && label == "PUBLIC KEY" => {
+            Some(AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+                curve: EllipticCurve::Ed25519,
+                x: encode(key),
+                ..Default::default()
+            }))
+        }
+        _ => None,
+    }
+}
+
+/// Signs a probe message with `encoding_key` and checks that `decoding_key` verifies it.
+fn is_pair(algorithm: Algorithm, encoding_key: &EncodingKey, decoding_key: &DecodingKey) -> bool {
+    const PROBE: &[u8] = b"lunna_actix_utils key pair probe";
+
+    crypto::sign(PROBE, encoding_key, algorithm)
+        .and_then(|signature| crypto::verify(&signature, PROBE, decoding_key, algorithm))
+        .unwrap_or(false)
+}
+
+fn key_algorithm(algorithm: Algorithm) -> KeyAlgorithm {
+    match algorithm {
+        Algorithm::HS256 => KeyAlgorithm::HS256,
+        Algorithm::HS384 => KeyAlgorithm::HS384,
+        Algorithm::HS512 => KeyAlgorithm::HS512,
+        Algorithm::ES256 => KeyAlgorithm::ES256,
+        Algorithm::ES384 => KeyAlgorithm::ES384,
+        Algorithm::RS256 => KeyAlgorithm::RS256,
+        Algorithm::RS384 => KeyAlgorithm::RS384,
+        Algorithm::RS512 => KeyAlgorithm::RS512,
+        Algorithm::PS256 => KeyAlgorithm::PS256,
+        Algorithm::PS384 => KeyAlgorithm::PS384,
+        Algorithm::PS512 => KeyAlgorithm::PS512,
+        Algorithm::EdDSA => KeyAlgorithm::EdDSA,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::test_keys::*;
+
+    #[test]
+    fn test_algorithm_is_pinned_to_key_type() {
+        assert!(JwtKey::rsa(Algorithm::PS256, None, RSA_PUBLIC_TEST_KEY.to_string()).is_ok());
+        assert!(JwtKey::rsa(Algorithm::ES256, None, RSA_PUBLIC_TEST_KEY.to_string()).is_err());
+        assert!(JwtKey::ec(Algorithm::ES256, None, EC_PUBLIC_TEST_KEY.to_string()).is_ok());
+        assert!(JwtKey::ec(Algorithm::EdDSA, None, EC_PUBLIC_TEST_KEY.to_string()).is_err());
+        assert!(JwtKey::hmac(Algorithm::HS512, HMAC_TEST_SECRET).is_ok());
+        assert!(JwtKey::hmac(Algorithm::RS256, HMAC_TEST_SECRET).is_err());
+    }
+
+    #[test]
+    fn test_invalid_pem_is_rejected() {
+        assert!(matches!(
+            JwtKey::rsa(Algorithm::RS256, None, "not a key".to_string()),
+            Err(AuthError::InvalidKey)
+        ));
+        assert!(matches!(
+            JwtKey::rsa(
+                Algorithm::RS256,
+                Some("not a key".to_string()),
+                RSA_PUBLIC_TEST_KEY.to_string()
+            ),
+            Err(AuthError::InvalidKey)
+        ));
+        assert!(matches!(
+            JwtKey::ec(Algorithm::ES256, None, RSA_PUBLIC_TEST_KEY.to_string()),
+            Err(AuthError::InvalidKey)
+        ));
+    }
+
+    #[test]
+    fn test_mismatched_key_pair_is_rejected() {
+        const OTHER_EC_PUBLIC_KEY: &str = r#"-----BEGIN PUBLIC KEY-----
+MFkwEwYHKoZIzj0CAQYIKoZIzj0DAQcDQgAE3G9u2QqQakFF3XwIlW+GGIuaaM1O
+uyt0WwyCwbmEpfWeTLkO14npneks9FGuhe/I8XBYca+/hfgMiv4HLa9STQ==
+-----END PUBLIC KEY-----"#;
+        const OTHER_ED25519_PUBLIC_KEY: &str = r#"-----BEGIN PUBLIC KEY-----
+MCowBQYDK2VwAyEAk9MpF+HWU+IBclWCLGSQjTaFFdJbuAENbK89l1OhPXo=
+-----END PUBLIC KEY-----"#;
+
+        assert!(
+            JwtKey::rsa(
+                Algorithm::RS256,
+                Some(RSA_PRIVATE_TEST_KEY.to_string()),
+                RSA_PUBLIC_TEST_KEY.to_string()
+            )
+            .is_ok()
+        );
+        assert!(
+            JwtKey::ec(
+                Algorithm::ES256,
+                Some(EC_PRIVATE_TEST_KEY.to_string()),
+                EC_PUBLIC_TEST_KEY.to_string()
+            )
+            .is_ok()
+        );
+        assert!(matches!(
+            JwtKey::ec(
+                Algorithm::ES256,
+                Some(EC_PRIVATE_TEST_KEY.to_string()),
+                OTHER_EC_PUBLIC_KEY.to_string()
+            ),
+            Err(AuthError::InvalidKey)
+        ));
+        assert!(matches!(
+            JwtKey::ed25519(
+                Some(ED25519_PRIVATE_TEST_KEY.to_string()),
+                OTHER_ED25519_PUBLIC_KEY.to_string()
+            ),
+            Err(AuthError::InvalidKey)
+        ));
+    }
+
+    #[test]
+    fn test_to_jwk() {
+        let rsa = JwtKey::rsa(Algorithm::RS256, None, RSA_PUBLIC_TEST_KEY.to_string()).unwrap();
+        let ec = JwtKey::ec(Algorithm::ES256, None, EC_PUBLIC_TEST_KEY.to_string()).unwrap();
+        let ed = JwtKey::ed25519(None, ED25519_PUBLIC_TEST_KEY.to_string()).unwrap();
+        let hmac = JwtKey::hmac(Algorithm::HS256, HMAC_TEST_SECRET).unwrap();
+
+        for (key, kid) in [(rsa, "rsa"), (ec, "ec"), (ed, "ed")] {
+            let jwk = key.to_jwk(kid).unwrap();
+
+            assert_eq!(jwk.common.key_id.as_deref(), Some(kid));
+            assert_eq!(jwk.common.public_key_use, Some(PublicKeyUse::Signature));
+            assert!(jwk.is_supported());
+            assert!(DecodingKey::from_jwk(&jwk).is_ok());
+        }
+        assert!(hmac.to_jwk("hmac").is_none());
+    }
+
+    #[test]
+    fn test_from_jwk() {
+        let rsa = JwtKey::rsa(Algorithm::PS384, None, RSA_PUBLIC_TEST_KEY.to_string()).unwrap();
+        let mut jwk = rsa.to_jwk("rsa").unwrap();
+
+        let key = JwtKey::from_jwk(&jwk).unwrap();
+        assert_eq!(key.algorithm(), Algorithm::PS384);
+        assert!(!key.can_sign());
+        assert_eq!(key.to_jwk("rsa"), Some(jwk.clone()));
+
+        jwk.common.key_algorithm = None;
+        assert_eq!(
+            JwtKey::from_jwk(&jwk).unwrap().algorithm(),
+            Algorithm::RS256
+        );
+
+        jwk.common.key_algorithm = Some(KeyAlgorithm::ES256);
+        assert!(matches!(JwtKey::from_jwk(&jwk), Err(AuthError::InvalidKey)));
+
+        jwk.common.key_algorithm = None;
+        jwk.common.public_key_use = Some(PublicKeyUse::Encryption);
+        assert!(matches!(JwtKey::from_jwk(&jwk), Err(AuthError::InvalidKey)));
+
+        let ec = JwtKey::ec(Algorithm::ES256, None, EC_PUBLIC_TEST_KEY.to_string()).unwrap();
+        let mut jwk = ec.to_jwk("ec").unwrap();
+        jwk.common.key_algorithm = None;
+        assert_eq!(
+            JwtKey::from_jwk(&jwk).unwrap().algorithm(),
+            Algorithm::ES256
+        );
+    }
+
+    #[test]
+    fn test_can_sign() {
+        let public_only = JwtKey::ed25519(None, ED25519_PUBLIC_TEST_KEY.to_string()).unwrap();
+        let key_pair = JwtKey::ed25519(
+            Some(ED25519_PRIVATE_TEST_KEY.to_string()),
+            ED25519_PUBLIC_TEST_KEY.to_string(),
+        )
+        .unwrap();
+
+        assert!(!public_only.can_sign());
+        assert!(public_only.encoding_key().is_none());
+        assert!(key_pair.can_sign());
+        assert!(
+            JwtKey::hmac(Algorithm::HS256, HMAC_TEST_SECRET)
+                .unwrap()
+                .can_sign()
+        );
+    }
+}
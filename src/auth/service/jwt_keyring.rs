@@ -0,0 +1,199 @@
+use crate::auth::error::AuthError;
+use crate::auth::service::jwt_key::JwtKey;
+use std::sync::{Arc, RwLock};
+
+/// The key id used by the single-key [`JwtService`](super::jwt_service::JwtService)
+/// constructors.
+pub const DEFAULT_KID: &str = "default";
+
+/// A set of [`JwtKey`]s identified by their `kid`.
+///
+/// New tokens are signed with the current key and carry its `kid` in the header.
+/// Tokens are verified with whichever non-retired key their `kid` names, so a signing
+/// key can be rotated without invalidating the tokens issued under the previous one.
+/// Keys can be added, promoted and retired at runtime through a shared reference.
+///
+/// # Example
+/// ```
+/// use jsonwebtoken::Algorithm;
+/// use lunna_actix_utils::auth::service::jwt_key::JwtKey;
+/// use lunna_actix_utils::auth::service::jwt_keyring::JwtKeyring;
+///
+/// let keyring = JwtKeyring::new("2025-01", JwtKey::hmac(Algorithm::HS256, b"old").unwrap());
+///
+/// keyring.add("2025-02", JwtKey::hmac(Algorithm::HS256, b"new").unwrap()).unwrap();
+/// keyring.promote("2025-02").unwrap();
+/// keyring.retire("2025-01").unwrap();
+///
+/// assert_eq!(keyring.current().0, "2025-02");
+/// assert!(keyring.verification_key("2025-01").is_none());
+/// ```
+pub struct JwtKeyring {
+    state: RwLock<KeyringState>,
+}
+
+struct KeyringState {
+    current: String,
+    keys: Vec<KeyringEntry>,
+}
+
+struct KeyringEntry {
+    kid: String,
+    key: Arc<JwtKey>,
+    retired: bool,
+}
+
+impl JwtKeyring {
+    /// Creates a keyring whose current key is `key`.
+    pub fn new(kid: &str, key: JwtKey) -> JwtKeyring {
+        JwtKeyring {
+            state: RwLock::new(KeyringState {
+                current: kid.to_string(),
+                keys: vec![KeyringEntry {
+                    kid: kid.to_string(),
+                    key: Arc::new(key),
+                    retired: false,
+                }],
+            }),
+        }
+    }
+
+    /// Adds a key that verifies tokens right away and can be promoted later.
+    ///
+    /// Fails with [`AuthError::InvalidKey`] if the `kid` is already taken.
+    pub fn add(&self, kid: &str, key: JwtKey) -> Result<(), AuthError> {
+        let mut state = self.write();
+
+        if state.entry(kid).is_some() {
+            return Err(AuthError::InvalidKey);
+        }
+
+        state.keys.push(KeyringEntry {
+            kid: kid.to_string(),
+            key: Arc::new(key),
+            retired: false,
+        });
+
+        Ok(())
+    }
+
+    /// Makes a key the one used to sign new tokens.
+    ///
+    /// Fails with [`AuthError::KeyNotFound`] if the key is unknown or retired.
+    pub fn promote(&self, kid: &str) -> Result<(), AuthError> {
+        let mut state = self.write();
+
+        match state.entry(kid) {
+            Some(entry) if !entry.retired => {
+                state.current = kid.to_string();
+                Ok(())
+            }
+            _ => Err(AuthError::KeyNotFound),
+        }
+    }
+
+    /// Stops accepting tokens signed with a key.
+    ///
+    /// The current key can't be retired; promote another one first.
+    pub fn retire(&self, kid: &str) -> Result<(), AuthError> {
+        let mut state = self.write();
+
+        if state.current == kid {
+            return Err(AuthError::InvalidKey);
+        }
+
+        match state.keys.iter_mut().find(|entry| entry.kid == kid) {
+            Some(entry) => {
+                entry.retired = true;
+                Ok(())
+            }
+            None => Err(AuthError::KeyNotFound),
+        }
+    }
+
+    /// Returns the `kid` and key used to sign new tokens.
+    pub fn current(&self) -> (String, Arc<JwtKey>) {
+        let state = self.read();
+        let entry = state
+            .entry(&state.current)
+            .expect("the current key is always in the keyring");
+
+        (entry.kid.clone(), entry.key.clone())
+    }
+
+    /// Returns the key for a `kid`, unless it is unknown or retired.
+    pub fn verification_key(&self, kid: &str) -> Option<Arc<JwtKey>> {
+        self.read()
+            .entry(kid)
+            .filter(|entry| !entry.retired)
+            .map(|entry| entry.key.clone())
+    }
+
+    /// Returns every non-retired key with its `kid`.
+    pub fn verification_keys(&self) -> Vec<(String, Arc<JwtKey>)> {
+        self.read()
+            .keys
+            .iter()
+            .filter(|entry| !entry.retired)
+            .map(|entry| (entry.kid.clone(), entry.key.clone()))
+            .collect()
+    }
+
+    fn read(&self) -> std::sync::RwLockReadGuard<'_, KeyringState> {
+        self.state
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn write(&self) -> std::sync::RwLockWriteGuard<'_, KeyringState> {
+        self.state
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl KeyringState {
+    fn entry(&self, kid: &str) -> Option<&KeyringEntry> {
+        self.keys.iter().find(|entry| entry.kid == kid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::test_keys::*;
+    use jsonwebtoken::Algorithm;
+
+    fn hmac_key() -> JwtKey {
+        JwtKey::hmac(Algorithm::HS256, HMAC_TEST_SECRET).unwrap()
+    }
+
+    #[test]
+    fn test_add_rejects_duplicate_kid() {
+        let keyring = JwtKeyring::new("a", hmac_key());
+
+        assert!(matches!(
+            keyring.add("a", hmac_key()),
+            Err(AuthError::InvalidKey)
+        ));
+        assert!(keyring.add("b", hmac_key()).is_ok());
+        assert_eq!(keyring.verification_keys().len(), 2);
+    }
+
+    #[test]
+    fn test_promote_and_retire() {
+        let keyring = JwtKeyring::new("a", hmac_key());
+        keyring.add("b", hmac_key()).unwrap();
+
+        assert!(matches!(keyring.retire("a"), Err(AuthError::InvalidKey)));
+        assert!(matches!(keyring.promote("c"), Err(AuthError::KeyNotFound)));
+
+        keyring.promote("b").unwrap();
+        keyring.retire("a").unwrap();
+
+        assert_eq!(keyring.current().0, "b");
+        assert!(keyring.verification_key("a").is_none());
+        assert!(keyring.verification_key("b").is_some());
+        assert!(matches!(keyring.promote("a"), Err(AuthError::KeyNotFound)));
+    }
+}
@@ -0,0 +1,116 @@
+#[cfg(feature = "jwt")]
+use jsonwebtoken::{Algorithm, Validation};
+use serde::{Deserialize, Serialize};
+
+/// A registered JWT claim (RFC 7519) that a token can be required to carry.
+///
+/// `exp` isn't listed because it is always required.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RegisteredClaim {
+    Iss,
+    Sub,
+    Aud,
+    Iat,
+    Nbf,
+    Jti,
+}
+
+/// How [`JwtService`](super::jwt_service::JwtService) checks the registered claims of a
+/// token, on top of its signature and expiry.
+///
+/// Setting an issuer or an audience makes the claim mandatory, so a token minted for
+/// one service (with its own `aud`) is rejected by every other one. By default any
+/// issuer and audience are accepted and there is no leeway.
+///
+/// # Example
+/// ```
+/// use lunna_actix_utils::auth::service::jwt_validation_config::{
+///     JwtValidationConfig, RegisteredClaim,
+/// };
+///
+/// let config: JwtValidationConfig = serde_json::from_str(
+///     r#"{ "issuer": "https://auth.lunna.dev", "audience": ["billing"], "required_claims": ["jti"] }"#,
+/// )
+/// .unwrap();
+///
+/// assert_eq!(config.leeway, 0);
+/// assert_eq!(config.required_claims, vec![RegisteredClaim::Jti]);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct JwtValidationConfig {
+    /// The `iss` tokens must carry. Any issuer is accepted when unset.
+    pub issuer: Option<String>,
+
+    /// The audiences this service answers to. A token must name at least one of them
+    /// in its `aud`. Any audience is accepted when empty.
+    pub audience: Vec<String>,
+
+    /// Seconds of clock skew tolerated when checking `exp` and `nbf`.
+    pub leeway: u64,
+
+    /// Claims a token must carry, whatever their value.
+    pub required_claims: Vec<RegisteredClaim>,
+}
+
+impl JwtValidationConfig {
+    pub fn with_issuer(mut self, issuer: &str) -> Self {
+        self.issuer = Some(issuer.to_string());
+        self
+    }
+
+    /// Adds an accepted audience.
+    pub fn with_audience(mut self, audience: &str) -> Self {
+        self.audience.push(audience.to_string());
+        self
+    }
+
+    pub fn with_leeway(mut self, leeway: u64) -> Self {
+        self.leeway = leeway;
+        self
+    }
+
+    pub fn with_required_claim(mut self, claim: RegisteredClaim) -> Self {
+        self.required_claims.push(claim);
+        self
+    }
+
+    /// Builds the `jsonwebtoken` validation for a key pinned to `algorithm`.
+    ///
+    /// `iat` and `jti` can't be required through it; see
+    /// [`JwtDataContainer::has_claim`](super::token_claims::JwtDataContainer::has_claim).
+    #[cfg(feature = "jwt")]
+    pub(crate) fn validation(&self, algorithm: Algorithm) -> Validation {
+        let mut validation = Validation::new(algorithm);
+        // `exp` and `nbf` are checked against the service's clock instead.
+        validation.validate_exp = false;
+        validation.validate_nbf = false;
+
+        let mut required = vec!["exp"];
+        for claim in &self.required_claims {
+            match claim {
+                RegisteredClaim::Iss => required.push("iss"),
+                RegisteredClaim::Sub => required.push("sub"),
+                RegisteredClaim::Aud => required.push("aud"),
+                RegisteredClaim::Nbf => required.push("nbf"),
+                RegisteredClaim::Iat | RegisteredClaim::Jti => {}
+            }
+        }
+
+        if let Some(issuer) = &self.issuer {
+            validation.set_issuer(&[issuer]);
+            required.push("iss");
+        }
+
+        if self.audience.is_empty() {
+            validation.validate_aud = false;
+        } else {
+            validation.set_audience(&self.audience);
+            required.push("aud");
+        }
+
+        validation.set_required_spec_claims(&required);
+        validation
+    }
+}
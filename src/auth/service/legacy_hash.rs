@@ -0,0 +1,196 @@
+use argon2::password_hash;
+
+/// The format of a stored password hash, detected from its PHC or modular-crypt prefix.
+///
+/// [`HashService::verify_password`](super::hash_service::HashService::verify_password)
+/// uses it to verify hashes imported from older systems. Every format other than
+/// [`HashFormat::Argon2`] is always reported as needing a rehash.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashFormat {
+    /// `$argon2id$`, `$argon2i$` or `$argon2d$`.
+    Argon2,
+    /// `$2a$`, `$2b$`, `$2x$` or `$2y$`. Requires the `bcrypt` feature.
+    Bcrypt,
+    /// `$scrypt$`. Requires the `scrypt` feature.
+    Scrypt,
+    /// `$pbkdf2-sha256$` or `$pbkdf2-sha512$`. Requires the `pbkdf2` feature.
+    Pbkdf2,
+    /// Anything else.
+    Unknown,
+}
+
+impl HashFormat {
+    pub fn detect(hashed_password: &str) -> HashFormat {
+        const PREFIXES: &[(&str, HashFormat)] = &[
+            ("$argon2id$", HashFormat::Argon2),
+            ("$argon2i$", HashFormat::Argon2),
+            ("$argon2d$", HashFormat::Argon2),
+            ("$2a$", HashFormat::Bcrypt),
+            ("$2b$", HashFormat::Bcrypt),
+            ("$2x$", HashFormat::Bcrypt),
+            ("$2y$", HashFormat::Bcrypt),
+            ("$scrypt$", HashFormat::Scrypt),
+            ("$pbkdf2-sha256$", HashFormat::Pbkdf2),
+            ("$pbkdf2-sha512$", HashFormat::Pbkdf2),
+        ];
+
+        PREFIXES
+            .iter()
+            .find(|(prefix, _)| hashed_password.starts_with(prefix))
+            .map(|(_, format)| *format)
+            .unwrap_or(HashFormat::Unknown)
+    }
+
+    /// Returns `true` if this build can verify hashes in this format.
+    pub fn is_supported(&self) -> bool {
+        match self {
+            HashFormat::Argon2 => true,
+            HashFormat::Bcrypt => cfg!(feature = "bcrypt"),
+            HashFormat::Scrypt => cfg!(feature = "scrypt"),
+            HashFormat::Pbkdf2 => cfg!(feature = "pbkdf2"),
+            HashFormat::Unknown => false,
+        }
+    }
+}
+
+/// Verifies a password against a non-Argon2 hash.
+///
+/// Fails with [`password_hash::Error::Algorithm`] if the format is unknown or its
+/// cargo feature is disabled.
+#[allow(unused_variables)]
+pub(crate) fn verify_legacy(
+    format: HashFormat,
+    password: &str,
+    hashed_password: &str,
+) -> Result<bool, password_hash::Error> {
+    match format {
+        #[cfg(feature = "bcrypt")]
+        HashFormat::Bcrypt => {
+            bcrypt::verify(password, hashed_password).map_err(|_| password_hash::Error::Crypto)
+        }
+        #[cfg(feature = "scrypt")]
+        HashFormat::Scrypt => {
+            use argon2::{PasswordHash, PasswordVerifier};
+
+            let parsed_hash = PasswordHash::new(hashed_password)?;
+            Ok(scrypt::Scrypt
+                .verify_password(password.as_bytes(), &parsed_hash)
+                .is_ok())
+        }
+        #[cfg(feature = "pbkdf2")]
+        HashFormat::Pbkdf2 => {
+            use argon2::{PasswordHash, PasswordVerifier};
+
+            let parsed_hash = PasswordHash::new(hashed_password)?;
+            Ok(pbkdf2::Pbkdf2
+                .verify_password(password.as_bytes(), &parsed_hash)
+                .is_ok())
+        }
+        _ => Err(password_hash::Error::Algorithm),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::service::hash_service::{HashService, PasswordVerification};
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(
+            HashFormat::detect("$argon2id$v=19$m=19456,t=2,p=1$c2FsdA$aGFzaA"),
+            HashFormat::Argon2
+        );
+        assert_eq!(
+            HashFormat::detect("$2b$04$abcdefghijklmnopqrstuu"),
+            HashFormat::Bcrypt
+        );
+        assert_eq!(
+            HashFormat::detect("$scrypt$ln=4,r=8,p=1$c2FsdA$aGFzaA"),
+            HashFormat::Scrypt
+        );
+        assert_eq!(
+            HashFormat::detect("$pbkdf2-sha256$i=1000,l=32$c2FsdA$aGFzaA"),
+            HashFormat::Pbkdf2
+        );
+        assert_eq!(HashFormat::detect("$md5$whatever"), HashFormat::Unknown);
+        assert_eq!(HashFormat::detect("invalid_hash"), HashFormat::Unknown);
+    }
+
+    #[test]
+    fn test_unknown_format_returns_error() {
+        let service = HashService::new();
+        let result = service.verify_password("password", "$md5$c2FsdA$aGFzaA");
+        assert!(matches!(result, Err(password_hash::Error::Algorithm)));
+    }
+
+    #[cfg(feature = "bcrypt")]
+    #[test]
+    fn test_bcrypt_needs_rehash() {
+        let service = HashService::new();
+        let hash = bcrypt::hash("password123", 4).unwrap();
+
+        assert_verifies_with_rehash(&service, &hash);
+    }
+
+    #[cfg(feature = "scrypt")]
+    #[test]
+    fn test_scrypt_needs_rehash() {
+        use argon2::PasswordHasher;
+        use argon2::password_hash::SaltString;
+        use argon2::password_hash::rand_core::OsRng;
+
+        let service = HashService::new();
+        let hash = scrypt::Scrypt
+            .hash_password_customized(
+                b"password123",
+                None,
+                None,
+                scrypt::Params::new(4, 8, 1, 32).unwrap(),
+                &SaltString::generate(&mut OsRng),
+            )
+            .unwrap()
+            .to_string();
+
+        assert_verifies_with_rehash(&service, &hash);
+    }
+
+    #[cfg(feature = "pbkdf2")]
+    #[test]
+    fn test_pbkdf2_needs_rehash() {
+        use argon2::PasswordHasher;
+        use argon2::password_hash::SaltString;
+        use argon2::password_hash::rand_core::OsRng;
+
+        let service = HashService::new();
+        let hash = pbkdf2::Pbkdf2
+            .hash_password_customized(
+                b"password123",
+                Some(pbkdf2::Algorithm::Pbkdf2Sha256.ident()),
+                None,
+                pbkdf2::Params {
+                    rounds: 1000,
+                    output_length: 32,
+                },
+                &SaltString::generate(&mut OsRng),
+            )
+            .unwrap()
+            .to_string();
+
+        assert!(hash.starts_with("$pbkdf2-sha256$"));
+        assert_verifies_with_rehash(&service, &hash);
+    }
+
+    #[allow(dead_code)]
+    fn assert_verifies_with_rehash(service: &HashService, hash: &str) {
+        assert_eq!(
+            service.verify_password("password123", hash).unwrap(),
+            PasswordVerification::NeedsRehash
+        );
+        assert_eq!(
+            service.verify_password("wrongpassword", hash).unwrap(),
+            PasswordVerification::Invalid
+        );
+        assert!(service.needs_rehash(hash));
+    }
+}
@@ -1,3 +1,31 @@
 pub mod auth_service;
+pub mod client_credentials;
+pub mod clock;
+pub mod hash_config;
 pub mod hash_service;
+#[cfg(feature = "jwe")]
+pub mod jwe_key;
+#[cfg(feature = "jwe")]
+pub mod jwe_service;
+#[cfg(feature = "jwks")]
+pub mod jwks_verifier;
+#[cfg(feature = "jwt")]
+pub mod jwt_key;
+#[cfg(feature = "jwt")]
+pub mod jwt_keyring;
+#[cfg(feature = "jwt")]
 pub mod jwt_service;
+pub mod jwt_validation_config;
+pub mod legacy_hash;
+#[cfg(feature = "paseto")]
+pub mod paseto_key;
+#[cfg(feature = "paseto")]
+pub mod paseto_service;
+pub mod pepper_keyring;
+pub mod refresh_token_service;
+pub mod refresh_token_store;
+pub mod revocation_store;
+pub mod token_claims;
+pub mod token_codec;
+pub mod token_cookie_config;
+pub mod token_exchange_service;
@@ -0,0 +1,373 @@
+use crate::auth::error::AuthError;
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use blake2::Blake2bMac;
+use blake2::digest::consts::{U32, U56};
+use blake2::digest::{KeyInit, Mac};
+use chacha20::XChaCha20;
+use chacha20::cipher::{KeyIvInit, StreamCipher};
+use ed25519_dalek::pkcs8::{DecodePrivateKey, DecodePublicKey};
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+
+const LOCAL_HEADER: &str = "v4.local.";
+const PUBLIC_HEADER: &str = "v4.public.";
+
+/// A key used by [`PasetoService`](super::paseto_service::PasetoService).
+///
+/// The key decides the token purpose: a local key issues `v4.local` tokens, encrypted
+/// and authenticated with a shared secret, and a public key issues `v4.public` tokens,
+/// signed with Ed25519. There is no algorithm to choose or to read from a token, so a
+/// token of the other purpose is simply rejected.
+///
+/// # Example
+/// ```
+/// use lunna_actix_utils::auth::service::paseto_key::PasetoKey;
+///
+/// let key = PasetoKey::local(&[7; 32]).unwrap();
+///
+/// assert!(key.can_sign());
+/// assert!(PasetoKey::local(b"too short").is_err());
+/// ```
+#[derive(Clone)]
+pub struct PasetoKey {
+    material: KeyMaterial,
+}
+
+#[derive(Clone)]
+enum KeyMaterial {
+    Local([u8; 32]),
+    Public {
+        signing: Option<Box<SigningKey>>,
+        verifying: VerifyingKey,
+    },
+}
+
+impl PasetoKey {
+    /// A 256-bit shared secret for `v4.local`. It both encrypts and decrypts.
+    pub fn local(secret: &[u8]) -> Result<PasetoKey, AuthError> {
+        let secret = secret.try_into().map_err(|_| AuthError::InvalidKey)?;
+
+        Ok(PasetoKey {
+            material: KeyMaterial::Local(secret),
+        })
+    }
+
+    /// An Ed25519 key for `v4.public`, in PKCS#8/SPKI PEM. Without a private key it can
+    /// only verify.
+    pub fn public(private_key: Option<String>, public_key: String) -> Result<PasetoKey, AuthError> {
+        let verifying =
+            VerifyingKey::from_public_key_pem(&public_key).map_err(|_| AuthError::InvalidKey)?;
+        let signing = private_key
+            .map(|private_key| {
+                SigningKey::from_pkcs8_pem(&private_key)
+                    .map(Box::new)
+                    .map_err(|_| AuthError::InvalidKey)
+            })
+            .transpose()?;
+
+        if signing
+            .as_ref()
+            .is_some_and(|signing| signing.verifying_key() != verifying)
+        {
+            return Err(AuthError::InvalidKey);
+        }
+
+        Ok(PasetoKey {
+            material: KeyMaterial::Public { signing, verifying },
+        })
+    }
+
+    /// Whether the key can issue tokens.
+    pub fn can_sign(&self) -> bool {
+        match &self.material {
+            KeyMaterial::Local(_) => true,
+            KeyMaterial::Public { signing, .. } => signing.is_some(),
+        }
+    }
+
+    /// The header of the tokens of this key, `v4.local.` or `v4.public.`.
+    pub fn header(&self) -> &'static str {
+        match &self.material {
+            KeyMaterial::Local(_) => LOCAL_HEADER,
+            KeyMaterial::Public { .. } => PUBLIC_HEADER,
+        }
+    }
+
+    /// Encrypts or signs a message into a token.
+    pub(crate) fn seal(
+        &self,
+        message: &[u8],
+        footer: &[u8],
+        implicit_assertion: &[u8],
+    ) -> Result<String, AuthError> {
+        let body = match &self.material {
+            KeyMaterial::Local(secret) => {
+                let mut nonce = [0u8; 32];
+                OsRng.fill_bytes(&mut nonce);
+                encrypt(secret, &nonce, message, footer, implicit_assertion)?
+            }
+            KeyMaterial::Public { signing, .. } => {
+                let signing = signing.as_ref().ok_or(AuthError::NoPrivateKey)?;
+                let signature = signing.sign(&pae(&[
+                    PUBLIC_HEADER.as_bytes(),
+                    message,
+                    footer,
+                    implicit_assertion,
+                ]));
+                [message, &signature.to_bytes()].concat()
+            }
+        };
+
+        let mut token = format!("{}{}", self.header(), URL_SAFE_NO_PAD.encode(body));
+        if !footer.is_empty() {
+            token.push('.');
+            token.push_str(&URL_SAFE_NO_PAD.encode(footer));
+        }
+
+        Ok(token)
+    }
+
+    /// Checks a token and returns its message.
+    pub(crate) fn open(
+        &self,
+        token: &str,
+        implicit_assertion: &[u8],
+    ) -> Result<Vec<u8>, AuthError> {
+        let header = self.header();
+        let token = token.strip_prefix(header).ok_or(AuthError::InvalidToken)?;
+        let (body, footer) = token.split_once('.').unwrap_or((token, ""));
+
+        let decode = |part: &str| {
+            URL_SAFE_NO_PAD
+                .decode(part)
+                .map_err(|_| AuthError::InvalidToken)
+        };
+        let body = decode(body)?;
+        let footer = decode(footer)?;
+
+        match &self.material {
+            KeyMaterial::Local(secret) => decrypt(secret, &body, &footer, implicit_assertion),
+            KeyMaterial::Public { verifying, .. } => {
+                let split = body
+                    .len()
+                    .checked_sub(Signature::BYTE_SIZE)
+                    .ok_or(AuthError::InvalidToken)?;
+                let (message, signature) = body.split_at(split);
+                let signature =
+                    Signature::from_slice(signature).map_err(|_| AuthError::InvalidToken)?;
+
+                verifying
+                    .verify_strict(
+                        &pae(&[header.as_bytes(), message, &footer, implicit_assertion]),
+                        &signature,
+                    )
+                    .map_err(|_| AuthError::InvalidToken)?;
+
+                Ok(message.to_vec())
+            }
+        }
+    }
+}
+
+/// `v4.local` encryption, returning `n || c || t`.
+fn encrypt(
+    secret: &[u8; 32],
+    nonce: &[u8; 32],
+    message: &[u8],
+    footer: &[u8],
+    implicit_assertion: &[u8],
+) -> Result<Vec<u8>, AuthError> {
+    let (mut cipher, auth_key) = local_keys(secret, nonce)?;
+
+    let mut ciphertext = message.to_vec();
+    cipher.apply_keystream(&mut ciphertext);
+
+    let tag = auth_key
+        .chain_update(pae(&[
+            LOCAL_HEADER.as_bytes(),
+            nonce,
+            &ciphertext,
+            footer,
+            implicit_assertion,
+        ]))
+        .finalize()
+        .into_bytes();
+
+    Ok([nonce.as_slice(), &ciphertext, &tag].concat())
+}
+
+/// `v4.local` decryption of `n || c || t`.
+fn decrypt(
+    secret: &[u8; 32],
+    body: &[u8],
+    footer: &[u8],
+    implicit_assertion: &[u8],
+) -> Result<Vec<u8>, AuthError> {
+    if body.len() < 64 {
+        return Err(AuthError::InvalidToken);
+    }
+    let (nonce, rest) = body.split_at(32);
+    let (ciphertext, tag) = rest.split_at(rest.len() - 32);
+    let nonce: &[u8; 32] = nonce.try_into().map_err(|_| AuthError::InvalidToken)?;
+
+    let (mut cipher, auth_key) = local_keys(secret, nonce)?;
+    auth_key
+        .chain_update(pae(&[
+            LOCAL_HEADER.as_bytes(),
+            nonce,
+            ciphertext,
+            footer,
+            implicit_assertion,
+        ]))
+        .verify_slice(tag)
+        .map_err(|_| AuthError::InvalidToken)?;
+
+    let mut message = ciphertext.to_vec();
+    cipher.apply_keystream(&mut message);
+
+    Ok(message)
+}
+
+/// Splits the secret into the encryption key and nonce, and the authentication key, of
+/// one token.
+fn local_keys(
+    secret: &[u8; 32],
+    nonce: &[u8; 32],
+) -> Result<(XChaCha20, Blake2bMac<U32>), AuthError> {
+    let derived = <Blake2bMac<U56> as KeyInit>::new_from_slice(secret)
+        .map_err(|_| AuthError::InvalidKey)?
+        .chain_update(b"paseto-encryption-key")
+        .chain_update(nonce)
+        .finalize()
+        .into_bytes();
+    let (encryption_key, encryption_nonce) = derived.split_at(32);
+
+    let cipher = XChaCha20::new(encryption_key.into(), encryption_nonce.into());
+    let auth_key = <Blake2bMac<U32> as KeyInit>::new_from_slice(secret)
+        .map_err(|_| AuthError::InvalidKey)?
+        .chain_update(b"paseto-auth-key-for-aead")
+        .chain_update(nonce)
+        .finalize()
+        .into_bytes();
+
+    let auth_key = <Blake2bMac<U32> as KeyInit>::new_from_slice(&auth_key)
+        .map_err(|_| AuthError::InvalidKey)?;
+
+    Ok((cipher, auth_key))
+}
+
+/// Pre-authentication encoding: every piece prefixed with its length, so that pieces
+/// can't be shifted into one another.
+fn pae(pieces: &[&[u8]]) -> Vec<u8> {
+    let le64 = |n: usize| (n as u64 & (u64::MAX >> 1)).to_le_bytes();
+
+    let mut encoded = le64(pieces.len()).to_vec();
+    for piece in pieces {
+        encoded.extend_from_slice(&le64(piece.len()));
+        encoded.extend_from_slice(piece);
+    }
+
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::test_keys::*;
+
+    #[test]
+    fn test_pae() {
+        assert_eq!(pae(&[]), [0, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(
+            pae(&[b"test"]),
+            [
+                1, 0, 0, 0, 0, 0, 0, 0, 4, 0, 0, 0, 0, 0, 0, 0, b't', b'e', b's', b't'
+            ]
+        );
+    }
+
+    /// Test vectors 4-E-1 and 4-S-1 of the PASETO specification.
+    #[test]
+    fn test_vectors() {
+        let message = br#"{"data":"this is a secret message","exp":"2022-01-01T00:00:00+00:00"}"#;
+        let secret: [u8; 32] = std::array::from_fn(|i| 0x70 + i as u8);
+        let body = encrypt(&secret, &[0; 32], message, b"", b"").unwrap();
+        assert_eq!(
+            format!("{LOCAL_HEADER}{}", URL_SAFE_NO_PAD.encode(body)),
+            "v4.local.AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAQAr68PS4AXe7If_ZgesdkUMvSwscFlAl1pk5HC0e8kApeaqMfGo_7OpBnwJOAbY9V7WU6abu74MmcUE8YWAiaArVI8XJ5hOb_4v9RmDkneN0S92dx0OW4pgy7omxgf3S8c3LlQg"
+        );
+
+        let public = hex("1eb9dbbbbc047c03fd70604e0071f0987e16b28b757225c11f00415d0e20b1a2");
+        let key = PasetoKey {
+            material: KeyMaterial::Public {
+                signing: None,
+                verifying: VerifyingKey::from_bytes(&public.try_into().unwrap()).unwrap(),
+            },
+        };
+        let token = "v4.public.eyJkYXRhIjoidGhpcyBpcyBhIHNpZ25lZCBtZXNzYWdlIiwiZXhwIjoiMjAyMi0wMS0wMVQwMDowMDowMCswMDowMCJ9bg_XBBzds8lTZShVlwwKSgeKpLT3yukTw6JUz3W4h_ExsQV-P0V54zemZDcAxFaSeef1QlXEFtkqxT1ciiQEDA";
+        assert_eq!(
+            key.open(token, b"").unwrap(),
+            br#"{"data":"this is a signed message","exp":"2022-01-01T00:00:00+00:00"}"#
+        );
+    }
+
+    fn hex(value: &str) -> Vec<u8> {
+        (0..value.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&value[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_local_round_trip() {
+        let key = PasetoKey::local(&[7; 32]).unwrap();
+        let token = key.seal(b"secret", b"", b"").unwrap();
+        assert!(token.starts_with("v4.local."));
+        assert_eq!(key.open(&token, b"").unwrap(), b"secret");
+
+        let token = key.seal(b"secret", b"kid", b"service").unwrap();
+        assert_eq!(key.open(&token, b"service").unwrap(), b"secret");
+        assert!(key.open(&token, b"other service").is_err());
+
+        let other = PasetoKey::local(&[8; 32]).unwrap();
+        assert!(other.open(&token, b"service").is_err());
+    }
+
+    #[test]
+    fn test_public_round_trip() {
+        let key = PasetoKey::public(
+            Some(ED25519_PRIVATE_TEST_KEY.to_string()),
+            ED25519_PUBLIC_TEST_KEY.to_string(),
+        )
+        .unwrap();
+        let verifier = PasetoKey::public(None, ED25519_PUBLIC_TEST_KEY.to_string()).unwrap();
+
+        let token = key.seal(b"signed", b"", b"").unwrap();
+        assert!(token.starts_with("v4.public."));
+        assert_eq!(verifier.open(&token, b"").unwrap(), b"signed");
+        assert!(matches!(
+            verifier.seal(b"signed", b"", b""),
+            Err(AuthError::NoPrivateKey)
+        ));
+
+        let mut tampered = token.into_bytes();
+        let last = tampered.len() - 10;
+        tampered[last] = if tampered[last] == b'A' { b'B' } else { b'A' };
+        assert!(
+            verifier
+                .open(&String::from_utf8(tampered).unwrap(), b"")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_purpose_is_pinned() {
+        let local = PasetoKey::local(&[7; 32]).unwrap();
+        let public = PasetoKey::public(None, ED25519_PUBLIC_TEST_KEY.to_string()).unwrap();
+
+        let token = local.seal(b"secret", b"", b"").unwrap();
+        assert!(public.open(&token, b"").is_err());
+        assert!(local.open(&token.replace("v4.", "v3."), b"").is_err());
+    }
+}
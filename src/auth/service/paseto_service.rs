@@ -0,0 +1,393 @@
+use crate::auth::error::AuthError;
+use crate::auth::service::clock::{Clock, SystemClock};
+use crate::auth::service::jwt_validation_config::JwtValidationConfig;
+use crate::auth::service::paseto_key::PasetoKey;
+use crate::auth::service::revocation_store::RevocationStore;
+use crate::auth::service::token_claims::{
+    ClaimsLayout, JwtDataContainer, check_claims, layout_claims, unlayout_claims,
+};
+use crate::auth::service::token_codec::TokenCodec;
+use async_trait::async_trait;
+use chrono::{DateTime, SecondsFormat};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::sync::Arc;
+
+/// The claims PASETO encodes as RFC 3339 date-times rather than seconds.
+const TIME_CLAIMS: [&str; 3] = ["exp", "iat", "nbf"];
+
+/// Issues and checks PASETO v4 tokens, `v4.local` or `v4.public` depending on the
+/// [`PasetoKey`].
+///
+/// It is the PASETO counterpart of [`JwtService`](super::jwt_service::JwtService):
+/// tokens carry the same [`JwtDataContainer`], checked with the same
+/// [`JwtValidationConfig`], clock, claims layout and revocation store. Only the
+/// encoding differs: `exp`, `iat` and `nbf` are RFC 3339 date-times, as PASETO
+/// requires, and there is no header for an attacker to pick an algorithm with.
+///
+/// An implicit assertion, such as the name of the service tokens are meant for, can be
+/// bound to every token without being sent in it.
+///
+/// # Example
+/// ```
+/// use lunna_actix_utils::auth::service::paseto_key::PasetoKey;
+/// use lunna_actix_utils::auth::service::paseto_service::PasetoService;
+/// use lunna_actix_utils::auth::service::token_claims::get_current_time;
+///
+/// let service = PasetoService::new(PasetoKey::local(&[7; 32]).unwrap());
+///
+/// let token = service.generate_token("lunna".to_string(), get_current_time() + 60).unwrap();
+/// assert!(token.starts_with("v4.local."));
+///
+/// let claims = service.verify_token::<String>(&token).unwrap();
+/// assert_eq!(claims.data, "lunna");
+/// ```
+pub struct PasetoService {
+    key: PasetoKey,
+    validation: JwtValidationConfig,
+    clock: Arc<dyn Clock>,
+    layout: ClaimsLayout,
+    revocation_store: Option<Arc<dyn RevocationStore>>,
+    implicit_assertion: Vec<u8>,
+}
+
+impl PasetoService {
+    pub fn new(key: PasetoKey) -> PasetoService {
+        PasetoService {
+            key,
+            validation: JwtValidationConfig::default(),
+            clock: Arc::new(SystemClock),
+            layout: ClaimsLayout::default(),
+            revocation_store: None,
+            implicit_assertion: Vec::new(),
+        }
+    }
+
+    pub fn with_validation(mut self, validation: JwtValidationConfig) -> Self {
+        self.validation = validation;
+        self
+    }
+
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    pub fn with_claims_layout(mut self, layout: ClaimsLayout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    /// Sets the denylist consulted by [`PasetoService::verify_token_async`] and filled
+    /// by [`PasetoService::revoke_token`].
+    pub fn with_revocation_store(mut self, revocation_store: Arc<dyn RevocationStore>) -> Self {
+        self.revocation_store = Some(revocation_store);
+        self
+    }
+
+    /// Binds every token to this value: issuer and verifier must use the same one.
+    pub fn with_implicit_assertion(mut self, implicit_assertion: &[u8]) -> Self {
+        self.implicit_assertion = implicit_assertion.to_vec();
+        self
+    }
+
+    pub fn generate_token<T>(&self, data: T, until: u64) -> Result<String, AuthError>
+    where
+        T: Serialize + DeserializeOwned,
+    {
+        self.generate_token_with_data_container(JwtDataContainer::new(data, until))
+    }
+
+    /// Issues a token for a container built with [`JwtDataContainer::new`], to set
+    /// registered claims such as the audience.
+    pub fn generate_token_with_data_container<T>(
+        &self,
+        mut data: JwtDataContainer<T>,
+    ) -> Result<String, AuthError>
+    where
+        T: Serialize + DeserializeOwned,
+    {
+        data.stamp(self.clock.now());
+
+        let mut claims = layout_claims(&data, self.layout)?;
+        let Value::Object(fields) = &mut claims else {
+            return Err(AuthError::InternalError);
+        };
+        for name in TIME_CLAIMS {
+            if let Some(value) = fields.get_mut(name) {
+                *value = Value::String(to_date_time(value).ok_or(AuthError::InternalError)?);
+            }
+        }
+
+        let message = serde_json::to_vec(&claims).map_err(|_| AuthError::InternalError)?;
+        self.key.seal(&message, b"", &self.implicit_assertion)
+    }
+
+    /// Verifies a token and its registered claims.
+    ///
+    /// Like [`JwtService::verify_token`](super::jwt_service::JwtService::verify_token),
+    /// this fails with [`AuthError::RevocationCheckRequired`] once a revocation store is
+    /// configured: use [`PasetoService::verify_token_async`] instead.
+    pub fn verify_token<T>(&self, token: &str) -> Result<JwtDataContainer<T>, AuthError>
+    where
+        T: Serialize + DeserializeOwned + Send + Sync,
+    {
+        if self.revocation_store.is_some() {
+            return Err(AuthError::RevocationCheckRequired);
+        }
+
+        self.verify_token_unchecked(token)
+    }
+
+    /// Verifies a token without consulting the revocation store.
+    fn verify_token_unchecked<T>(&self, token: &str) -> Result<JwtDataContainer<T>, AuthError>
+    where
+        T: Serialize + DeserializeOwned,
+    {
+        let message = self.key.open(token, &self.implicit_assertion)?;
+
+        let mut claims: Value =
+            serde_json::from_slice(&message).map_err(|_| AuthError::InvalidToken)?;
+        let Value::Object(fields) = &mut claims else {
+            return Err(AuthError::InvalidToken);
+        };
+        for name in TIME_CLAIMS {
+            if let Some(value) = fields.get_mut(name) {
+                *value = Value::from(from_date_time(value).ok_or(AuthError::InvalidToken)?);
+            }
+        }
+
+        let claims = unlayout_claims::<T>(claims, self.layout)?;
+        check_claims(&claims, &self.validation, self.clock.now())?;
+
+        Ok(claims)
+    }
+
+    /// Verifies a token like [`PasetoService::verify_token`], then rejects it with
+    /// [`AuthError::TokenRevoked`] if its `jti` is in the revocation store.
+    ///
+    /// With a revocation store, tokens without a `jti` are rejected with
+    /// [`AuthError::InvalidToken`].
+    pub async fn verify_token_async<T>(&self, token: &str) -> Result<JwtDataContainer<T>, AuthError>
+    where
+        T: Serialize + DeserializeOwned + Send + Sync,
+    {
+        let claims = self.verify_token_unchecked::<T>(token)?;
+
+        if let Some(store) = &self.revocation_store {
+            // A token without a `jti` could never be revoked.
+            let jti = claims.jwt_id().ok_or(AuthError::InvalidToken)?;
+            if store.is_revoked(jti).await? {
+                return Err(AuthError::TokenRevoked);
+            }
+        }
+
+        Ok(claims)
+    }
+
+    /// Revokes a token until it expires. Fails with [`AuthError::InternalError`] if no
+    /// revocation store is configured.
+    pub async fn revoke_token(&self, token: &str) -> Result<(), AuthError> {
+        let Some(store) = &self.revocation_store else {
+            return Err(AuthError::InternalError);
+        };
+
+        let claims = self.verify_token_unchecked::<Value>(token)?;
+        let jti = claims.jwt_id().ok_or(AuthError::InvalidToken)?;
+
+        store.revoke(jti, claims.expires_at()).await
+    }
+}
+
+#[async_trait]
+impl TokenCodec for PasetoService {
+    fn encode(&self, claims: JwtDataContainer<Value>) -> Result<String, AuthError> {
+        self.generate_token_with_data_container(claims)
+    }
+
+    fn decode(&self, token: &str) -> Result<JwtDataContainer<Value>, AuthError> {
+        self.verify_token(token)
+    }
+
+    async fn decode_async(&self, token: &str) -> Result<JwtDataContainer<Value>, AuthError> {
+        self.verify_token_async(token).await
+    }
+
+    async fn revoke(&self, token: &str) -> Result<(), AuthError> {
+        self.revoke_token(token).await
+    }
+}
+
+/// Seconds since the epoch to an RFC 3339 date-time.
+fn to_date_time(seconds: &Value) -> Option<String> {
+    let seconds = i64::try_from(seconds.as_u64()?).ok()?;
+    let date_time = DateTime::from_timestamp(seconds, 0)?;
+
+    Some(date_time.to_rfc3339_opts(SecondsFormat::Secs, true))
+}
+
+/// An RFC 3339 date-time to seconds since the epoch.
+fn from_date_time(date_time: &Value) -> Option<u64> {
+    let date_time = DateTime::parse_from_rfc3339(date_time.as_str()?).ok()?;
+
+    u64::try_from(date_time.timestamp()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::service::clock::MockClock;
+    use crate::auth::service::jwt_validation_config::RegisteredClaim;
+    use crate::auth::service::revocation_store::InMemoryRevocationStore;
+    use crate::auth::test_keys::*;
+    use base64::Engine;
+    use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+    use serde::Deserialize;
+    use std::time::Duration;
+
+    fn public_service(clock: Arc<MockClock>) -> PasetoService {
+        let key = PasetoKey::public(
+            Some(ED25519_PRIVATE_TEST_KEY.to_string()),
+            ED25519_PUBLIC_TEST_KEY.to_string(),
+        )
+        .unwrap();
+        PasetoService::new(key).with_clock(clock)
+    }
+
+    #[test]
+    fn test_time_claims_are_date_times() {
+        let clock = Arc::new(MockClock::new(1_640_995_200));
+        let service = public_service(clock.clone());
+
+        let token = service
+            .generate_token("lunna".to_string(), clock.now() + 60)
+            .unwrap();
+        let body = URL_SAFE_NO_PAD
+            .decode(token.strip_prefix("v4.public.").unwrap())
+            .unwrap();
+        let claims: Value = serde_json::from_slice(&body[..body.len() - 64]).unwrap();
+
+        assert_eq!(claims["exp"], "2022-01-01T00:01:00Z");
+        assert_eq!(claims["iat"], "2022-01-01T00:00:00Z");
+
+        let claims = service.verify_token::<String>(&token).unwrap();
+        assert_eq!(claims.expires_at(), clock.now() + 60);
+        assert_eq!(claims.issued_at(), Some(clock.now()));
+    }
+
+    #[test]
+    fn test_validation() {
+        let clock = Arc::new(MockClock::new(1_700_000_000));
+        let service = public_service(clock.clone()).with_validation(
+            JwtValidationConfig::default()
+                .with_issuer("https://auth.lunna.dev")
+                .with_audience("billing")
+                .with_required_claim(RegisteredClaim::Sub),
+        );
+
+        let container = JwtDataContainer::new("lunna".to_string(), clock.now() + 60)
+            .with_issuer("https://auth.lunna.dev")
+            .with_audience("billing");
+        let token = service
+            .generate_token_with_data_container(container.clone().with_subject("42"))
+            .unwrap();
+        assert!(service.verify_token::<String>(&token).is_ok());
+
+        for container in [
+            container.clone(),
+            JwtDataContainer::new("lunna".to_string(), clock.now() + 60)
+                .with_issuer("https://auth.lunna.dev")
+                .with_audience("other")
+                .with_subject("42"),
+            JwtDataContainer::new("lunna".to_string(), clock.now() + 60)
+                .with_audience("billing")
+                .with_subject("42"),
+        ] {
+            let token = service
+                .generate_token_with_data_container(container)
+                .unwrap();
+            assert!(matches!(
+                service.verify_token::<String>(&token),
+                Err(AuthError::InvalidToken)
+            ));
+        }
+
+        clock.advance(Duration::from_secs(61));
+        assert!(matches!(
+            service.verify_token::<String>(&token),
+            Err(AuthError::TokenExpired)
+        ));
+    }
+
+    #[test]
+    fn test_flat_layout() {
+        #[derive(Serialize, Deserialize)]
+        struct Claims {
+            email: String,
+        }
+
+        let clock = Arc::new(MockClock::new(1_700_000_000));
+        let service = PasetoService::new(PasetoKey::local(&[7; 32]).unwrap())
+            .with_clock(clock.clone())
+            .with_claims_layout(ClaimsLayout::Flat);
+
+        let claims = Claims {
+            email: "lunna@lunna.dev".to_string(),
+        };
+        let token = service.generate_token(claims, clock.now() + 60).unwrap();
+
+        let claims = service.verify_token::<Claims>(&token).unwrap();
+        assert_eq!(claims.data.email, "lunna@lunna.dev");
+    }
+
+    #[tokio::test]
+    async fn test_revocation() {
+        let clock = Arc::new(MockClock::new(1_700_000_000));
+        let store = Arc::new(InMemoryRevocationStore::new().with_clock(clock.clone()));
+        let service = public_service(clock.clone()).with_revocation_store(store);
+
+        let token = service
+            .generate_token("lunna".to_string(), clock.now() + 60)
+            .unwrap();
+        service.revoke_token(&token).await.unwrap();
+
+        assert!(matches!(
+            service.verify_token::<String>(&token),
+            Err(AuthError::RevocationCheckRequired)
+        ));
+        assert!(matches!(
+            service.verify_token_async::<String>(&token).await,
+            Err(AuthError::TokenRevoked)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_token_without_jti_is_rejected_with_a_store() {
+        let clock = Arc::new(MockClock::new(1_700_000_000));
+        let service = public_service(clock.clone())
+            .with_revocation_store(Arc::new(InMemoryRevocationStore::new()));
+
+        let message = serde_json::json!({ "data": "lunna", "exp": "2100-01-01T00:00:00Z" });
+        let without_jti = service
+            .key
+            .seal(&serde_json::to_vec(&message).unwrap(), b"", b"")
+            .unwrap();
+
+        assert!(matches!(
+            service.verify_token_async::<String>(&without_jti).await,
+            Err(AuthError::InvalidToken)
+        ));
+    }
+
+    #[test]
+    fn test_token_codec() {
+        let clock = Arc::new(MockClock::new(1_700_000_000));
+        let codec: Arc<dyn TokenCodec> = Arc::new(public_service(clock.clone()));
+
+        let token = codec
+            .generate_token("lunna".to_string(), clock.now() + 60)
+            .unwrap();
+        assert_eq!(codec.verify_token::<String>(&token).unwrap().data, "lunna");
+    }
+}
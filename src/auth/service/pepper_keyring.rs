@@ -0,0 +1,117 @@
+use argon2::{Params, password_hash};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// A server-side secret mixed into password hashes through Argon2's secret parameter.
+///
+/// The `id` is written to the `keyid` field of every hash made with this pepper,
+/// so it must be at most [`Params::MAX_KEYID_LEN`] bytes long.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Pepper {
+    /// Identifier stored in the hash.
+    pub id: String,
+
+    /// The secret itself.
+    pub secret: String,
+
+    /// Retired peppers still verify, but their hashes are reported as needing a rehash.
+    #[serde(default)]
+    pub retired: bool,
+}
+
+impl fmt::Debug for Pepper {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Pepper")
+            .field("id", &self.id)
+            .field("secret", &"<redacted>")
+            .field("retired", &self.retired)
+            .finish()
+    }
+}
+
+/// The set of peppers known to [`HashService`](super::hash_service::HashService).
+///
+/// New hashes use the `current` pepper. Hashes made with any other pepper in the
+/// keyring keep verifying, which lets several peppers be active during a rotation.
+///
+/// # Example
+/// ```
+/// use lunna_actix_utils::auth::service::pepper_keyring::PepperKeyring;
+///
+/// let keyring = PepperKeyring::new("2025", "new secret")
+///     .with_pepper("2024", "old secret")
+///     .with_retired_pepper("2023", "leaked secret");
+///
+/// assert_eq!(keyring.current().unwrap().id, "2025");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PepperKeyring {
+    /// The id of the pepper used for new hashes, if any.
+    pub current: Option<String>,
+
+    /// Every known pepper, including the current one.
+    pub peppers: Vec<Pepper>,
+}
+
+impl PepperKeyring {
+    /// Creates a keyring that hashes new passwords with the given pepper.
+    pub fn new(id: &str, secret: &str) -> Self {
+        PepperKeyring {
+            current: Some(id.to_string()),
+            ..PepperKeyring::default()
+        }
+        .with_pepper(id, secret)
+    }
+
+    /// Adds a pepper that is still accepted for verification.
+    pub fn with_pepper(mut self, id: &str, secret: &str) -> Self {
+        self.peppers.push(Pepper {
+            id: id.to_string(),
+            secret: secret.to_string(),
+            retired: false,
+        });
+        self
+    }
+
+    /// Adds a pepper whose hashes verify but need a rehash.
+    pub fn with_retired_pepper(mut self, id: &str, secret: &str) -> Self {
+        self.peppers.push(Pepper {
+            id: id.to_string(),
+            secret: secret.to_string(),
+            retired: true,
+        });
+        self
+    }
+
+    /// Returns the pepper used for new hashes.
+    pub fn current(&self) -> Option<&Pepper> {
+        self.current.as_deref().and_then(|id| self.get(id))
+    }
+
+    /// Looks up a pepper by the id stored in a hash.
+    pub fn get(&self, id: &str) -> Option<&Pepper> {
+        self.peppers.iter().find(|pepper| pepper.id == id)
+    }
+
+    /// Checks that every id fits in a hash and that the current pepper exists
+    /// and is not retired.
+    pub fn validate(&self) -> Result<(), password_hash::Error> {
+        let invalid =
+            password_hash::Error::ParamValueInvalid(password_hash::errors::InvalidValue::Malformed);
+
+        if self
+            .peppers
+            .iter()
+            .any(|pepper| pepper.id.is_empty() || pepper.id.len() > Params::MAX_KEYID_LEN)
+        {
+            return Err(invalid);
+        }
+
+        match (&self.current, self.current()) {
+            (None, _) => Ok(()),
+            (Some(_), Some(pepper)) if !pepper.retired => Ok(()),
+            _ => Err(invalid),
+        }
+    }
+}
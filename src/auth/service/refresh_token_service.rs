@@ -0,0 +1,338 @@
+use crate::auth::error::AuthError;
+use crate::auth::service::clock::{Clock, SystemClock};
+use crate::auth::service::refresh_token_store::{RefreshTokenRecord, RefreshTokenStore};
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// The result of a successful [`RefreshTokenService::rotate`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RefreshTokenRotation {
+    /// The long token that replaces the one presented.
+    pub token: String,
+    /// Who the token family belongs to.
+    pub subject: String,
+}
+
+/// Issues single-use long tokens and rotates them on every renew.
+///
+/// Each renew consumes the presented token and hands out a new one of the same family.
+/// A token that was already rotated can only be presented again if it was copied, so
+/// when that happens the whole family is revoked and [`AuthError::TokenReused`] is
+/// returned: both the thief and the legitimate client have to log in again.
+///
+/// Rotation alone would keep a family alive forever, so a family can't be rotated past
+/// a maximum age, after which the user has to log in again.
+///
+/// Long tokens are opaque random strings, not JWTs. Only their SHA-256 digest reaches
+/// the [`RefreshTokenStore`].
+///
+/// # Example
+/// ```
+/// use lunna_actix_utils::auth::error::AuthError;
+/// use lunna_actix_utils::auth::service::refresh_token_service::RefreshTokenService;
+/// use lunna_actix_utils::auth::service::refresh_token_store::InMemoryRefreshTokenStore;
+/// use std::sync::Arc;
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let service = RefreshTokenService::new(Arc::new(InMemoryRefreshTokenStore::new()));
+///
+/// let login = service.issue("user-42").await.unwrap();
+/// let renewed = service.rotate(&login).await.unwrap();
+/// assert_eq!(renewed.subject, "user-42");
+///
+/// // The first token was stolen and replayed.
+/// assert!(matches!(service.rotate(&login).await, Err(AuthError::TokenReused)));
+/// assert!(service.rotate(&renewed.token).await.is_err());
+/// # }
+/// ```
+pub struct RefreshTokenService {
+    store: Arc<dyn RefreshTokenStore>,
+    ttl: u64,
+    max_family_age: u64,
+    prune_interval: u64,
+    last_prune: AtomicU64,
+    clock: Arc<dyn Clock>,
+}
+
+impl RefreshTokenService {
+    pub fn new(store: Arc<dyn RefreshTokenStore>) -> RefreshTokenService {
+        RefreshTokenService {
+            store,
+            ttl: 30 * 24 * 60 * 60,
+            max_family_age: 90 * 24 * 60 * 60,
+            prune_interval: 60,
+            last_prune: AtomicU64::new(0),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Sets how long, in seconds, a long token is valid after it is issued. Defaults to
+    /// 30 days.
+    pub fn with_ttl(mut self, ttl: u64) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Sets how long, in seconds, a family can be rotated after the login that started
+    /// it. Defaults to 90 days.
+    pub fn with_max_family_age(mut self, max_family_age: u64) -> Self {
+        self.max_family_age = max_family_age;
+        self
+    }
+
+    /// Sets how often, in seconds, expired tokens are pruned from the store. Defaults
+    /// to 60.
+    pub fn with_prune_interval(mut self, prune_interval: u64) -> Self {
+        self.prune_interval = prune_interval;
+        self
+    }
+
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Starts a new token family, usually on login or registration.
+    pub async fn issue(&self, subject: &str) -> Result<String, AuthError> {
+        self.insert(random_token(), subject.to_string(), self.clock.now())
+            .await
+    }
+
+    /// Exchanges a long token for a new one of the same family.
+    ///
+    /// Fails with [`AuthError::TokenNotFound`] for unknown or revoked tokens,
+    /// [`AuthError::TokenExpired`] for expired ones or ones whose family is too old, and
+    /// [`AuthError::TokenReused`], after revoking the family, for tokens that were
+    /// already rotated.
+    pub async fn rotate(&self, token: &str) -> Result<RefreshTokenRotation, AuthError> {
+        let token_hash = hash_token(token);
+        let record = self
+            .store
+            .get(&token_hash)
+            .await?
+            .ok_or(AuthError::TokenNotFound)?;
+
+        let now = self.clock.now();
+        if record.expires_at < now || self.family_expires_at(&record) < now {
+            return Err(AuthError::TokenExpired);
+        }
+
+        if !self.store.mark_rotated(&token_hash).await? {
+            self.store.revoke_family(&record.family_id).await?;
+            return Err(AuthError::TokenReused);
+        }
+
+        let token = self
+            .insert(
+                record.family_id,
+                record.subject.clone(),
+                record.family_issued_at,
+            )
+            .await?;
+
+        Ok(RefreshTokenRotation {
+            token,
+            subject: record.subject,
+        })
+    }
+
+    /// Revokes the family of a long token, usually on logout.
+    pub async fn revoke(&self, token: &str) -> Result<(), AuthError> {
+        let record = self
+            .store
+            .get(&hash_token(token))
+            .await?
+            .ok_or(AuthError::TokenNotFound)?;
+
+        self.store.revoke_family(&record.family_id).await
+    }
+
+    /// When the family of `record` can no longer be rotated.
+    fn family_expires_at(&self, record: &RefreshTokenRecord) -> u64 {
+        record.family_issued_at.saturating_add(self.max_family_age)
+    }
+
+    async fn insert(
+        &self,
+        family_id: String,
+        subject: String,
+        family_issued_at: u64,
+    ) -> Result<String, AuthError> {
+        let now = self.clock.now();
+        let last_prune = self.last_prune.load(Ordering::Relaxed);
+
+        if now.saturating_sub(last_prune) >= self.prune_interval
+            && self
+                .last_prune
+                .compare_exchange(last_prune, now, Ordering::Relaxed, Ordering::Relaxed)
+                .is_ok()
+        {
+            self.store.prune(now).await?;
+        }
+
+        let token = random_token();
+        self.store
+            .insert(RefreshTokenRecord {
+                token_hash: hash_token(&token),
+                family_id,
+                subject,
+                family_issued_at,
+                // No token outlives its family.
+                expires_at: now
+                    .saturating_add(self.ttl)
+                    .min(family_issued_at.saturating_add(self.max_family_age)),
+                rotated: false,
+            })
+            .await?;
+
+        Ok(token)
+    }
+}
+
+/// 256 random bits, encoded as base64url.
+pub(crate) fn random_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// The key a long token is stored under: its SHA-256 digest, encoded as base64url.
+fn hash_token(token: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(token.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::service::clock::MockClock;
+    use crate::auth::service::refresh_token_store::InMemoryRefreshTokenStore;
+    use std::time::Duration;
+
+    fn service() -> (RefreshTokenService, Arc<InMemoryRefreshTokenStore>) {
+        let store = Arc::new(InMemoryRefreshTokenStore::new());
+        (RefreshTokenService::new(store.clone()), store)
+    }
+
+    #[tokio::test]
+    async fn test_rotation_chain() {
+        let (service, store) = service();
+
+        let mut token = service.issue("42").await.unwrap();
+        for _ in 0..3 {
+            let rotation = service.rotate(&token).await.unwrap();
+            assert_eq!(rotation.subject, "42");
+            assert_ne!(rotation.token, token);
+            token = rotation.token;
+        }
+
+        assert_eq!(store.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_reuse_revokes_family() {
+        let (service, store) = service();
+
+        let first = service.issue("42").await.unwrap();
+        let other_session = service.issue("42").await.unwrap();
+        let second = service.rotate(&first).await.unwrap().token;
+
+        assert!(matches!(
+            service.rotate(&first).await,
+            Err(AuthError::TokenReused)
+        ));
+        assert!(matches!(
+            service.rotate(&second).await,
+            Err(AuthError::TokenNotFound)
+        ));
+
+        // Other logins of the same user are a different family.
+        assert!(service.rotate(&other_session).await.is_ok());
+        assert_eq!(store.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_expired_token() {
+        let clock = Arc::new(MockClock::new(1_000));
+        let (service, _) = service();
+        let service = service.with_ttl(60).with_clock(clock.clone());
+
+        let token = service.issue("42").await.unwrap();
+        clock.advance(Duration::from_secs(61));
+
+        assert!(matches!(
+            service.rotate(&token).await,
+            Err(AuthError::TokenExpired)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_family_max_age() {
+        let clock = Arc::new(MockClock::new(1_000));
+        let (service, _) = service();
+        let service = service
+            .with_ttl(60)
+            .with_max_family_age(100)
+            .with_clock(clock.clone());
+
+        let mut token = service.issue("42").await.unwrap();
+        for _ in 0..2 {
+            clock.advance(Duration::from_secs(45));
+            token = service.rotate(&token).await.unwrap().token;
+        }
+
+        // Each renew was within the ttl, but the family is now too old.
+        clock.advance(Duration::from_secs(45));
+        assert!(matches!(
+            service.rotate(&token).await,
+            Err(AuthError::TokenExpired)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_expired_tokens_are_pruned() {
+        let clock = Arc::new(MockClock::new(1_000));
+        let (service, store) = service();
+        let service = service.with_ttl(60).with_clock(clock.clone());
+
+        service.issue("a").await.unwrap();
+        clock.advance(Duration::from_secs(120));
+        service.issue("b").await.unwrap();
+
+        assert_eq!(store.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_store_only_sees_digests() {
+        let (service, store) = service();
+
+        let token = service.issue("42").await.unwrap();
+
+        assert!(store.get(&token).await.unwrap().is_none());
+        let record = store.get(&hash_token(&token)).await.unwrap().unwrap();
+        assert_eq!(record.token_hash, hash_token(&token));
+        assert_eq!(record.subject, "42");
+    }
+
+    #[tokio::test]
+    async fn test_revoke() {
+        let (service, _) = service();
+
+        let token = service.issue("42").await.unwrap();
+        let renewed = service.rotate(&token).await.unwrap().token;
+        service.revoke(&renewed).await.unwrap();
+
+        assert!(matches!(
+            service.rotate(&renewed).await,
+            Err(AuthError::TokenNotFound)
+        ));
+        assert!(matches!(
+            service.revoke("unknown").await,
+            Err(AuthError::TokenNotFound)
+        ));
+    }
+}
@@ -0,0 +1,108 @@
+use crate::auth::error::AuthError;
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// A long token as tracked by [`RefreshTokenService`](super::refresh_token_service::RefreshTokenService).
+///
+/// Every token descends from the one issued at login through a chain of rotations.
+/// Together they form a family that is revoked as a whole when reuse is detected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RefreshTokenRecord {
+    /// The SHA-256 digest of the token, never the token itself.
+    pub token_hash: String,
+    /// Shared by every token rotated from the same login.
+    pub family_id: String,
+    /// Who the token was issued to, such as a user id.
+    pub subject: String,
+    /// Seconds since the epoch when the family was started, that is the login.
+    pub family_issued_at: u64,
+    /// Seconds since the epoch after which the token is no longer accepted.
+    pub expires_at: u64,
+    /// Whether the token was already exchanged for a new one.
+    pub rotated: bool,
+}
+
+/// Storage for refresh tokens and their families.
+///
+/// Implementations only ever see SHA-256 digests of the tokens, computed by
+/// [`RefreshTokenService`](super::refresh_token_service::RefreshTokenService): a leaked
+/// store holds no token that can be presented as-is.
+#[async_trait]
+pub trait RefreshTokenStore: Send + Sync {
+    async fn insert(&self, record: RefreshTokenRecord) -> Result<(), AuthError>;
+
+    async fn get(&self, token_hash: &str) -> Result<Option<RefreshTokenRecord>, AuthError>;
+
+    /// Marks a token as rotated, returning `false` if it already was.
+    ///
+    /// Must be atomic: when two requests race with the same token, exactly one of them
+    /// gets `true`.
+    async fn mark_rotated(&self, token_hash: &str) -> Result<bool, AuthError>;
+
+    /// Removes every token of the family, rotated or not.
+    async fn revoke_family(&self, family_id: &str) -> Result<(), AuthError>;
+
+    /// Removes the tokens that expired before `now`.
+    async fn prune(&self, now: u64) -> Result<(), AuthError>;
+}
+
+/// A [`RefreshTokenStore`] kept in memory, for a single instance or for tests.
+#[derive(Default)]
+pub struct InMemoryRefreshTokenStore {
+    records: Mutex<HashMap<String, RefreshTokenRecord>>,
+}
+
+impl InMemoryRefreshTokenStore {
+    pub fn new() -> InMemoryRefreshTokenStore {
+        Self::default()
+    }
+
+    /// The number of tokens currently stored, rotated or not.
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, RefreshTokenRecord>> {
+        self.records
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[async_trait]
+impl RefreshTokenStore for InMemoryRefreshTokenStore {
+    async fn insert(&self, record: RefreshTokenRecord) -> Result<(), AuthError> {
+        self.lock().insert(record.token_hash.clone(), record);
+        Ok(())
+    }
+
+    async fn get(&self, token_hash: &str) -> Result<Option<RefreshTokenRecord>, AuthError> {
+        Ok(self.lock().get(token_hash).cloned())
+    }
+
+    async fn mark_rotated(&self, token_hash: &str) -> Result<bool, AuthError> {
+        match self.lock().get_mut(token_hash) {
+            Some(record) if !record.rotated => {
+                record.rotated = true;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn revoke_family(&self, family_id: &str) -> Result<(), AuthError> {
+        self.lock()
+            .retain(|_, record| record.family_id != family_id);
+        Ok(())
+    }
+
+    async fn prune(&self, now: u64) -> Result<(), AuthError> {
+        self.lock().retain(|_, record| record.expires_at >= now);
+        Ok(())
+    }
+}
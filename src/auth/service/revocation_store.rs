@@ -0,0 +1,164 @@
+use crate::auth::error::AuthError;
+use crate::auth::service::clock::{Clock, SystemClock};
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// A denylist of revoked tokens, keyed by their `jti`.
+///
+/// An entry only has to outlive the token it revokes: once `expires_at` has passed the
+/// token is rejected as expired anyway, so implementations prune such entries on their
+/// own instead of growing forever.
+#[async_trait]
+pub trait RevocationStore: Send + Sync {
+    /// Revokes the token with this `jti` until `expires_at`, its `exp`.
+    async fn revoke(&self, jti: &str, expires_at: u64) -> Result<(), AuthError>;
+
+    /// Returns `true` if the token with this `jti` was revoked and hasn't expired yet.
+    async fn is_revoked(&self, jti: &str) -> Result<bool, AuthError>;
+}
+
+/// A [`RevocationStore`] kept in memory, for a single instance or for tests.
+///
+/// Expired entries are pruned whenever a token is revoked, at most once per
+/// [`InMemoryRevocationStore::with_prune_interval`] seconds.
+///
+/// # Example
+/// ```
+/// use lunna_actix_utils::auth::service::revocation_store::{
+///     InMemoryRevocationStore, RevocationStore,
+/// };
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let store = InMemoryRevocationStore::new();
+/// store.revoke("3f2a", u64::MAX).await.unwrap();
+///
+/// assert!(store.is_revoked("3f2a").await.unwrap());
+/// # }
+/// ```
+pub struct InMemoryRevocationStore {
+    entries: Mutex<Entries>,
+    prune_interval: u64,
+    clock: Arc<dyn Clock>,
+}
+
+struct Entries {
+    revoked: HashMap<String, u64>,
+    last_prune: u64,
+}
+
+impl InMemoryRevocationStore {
+    pub fn new() -> InMemoryRevocationStore {
+        InMemoryRevocationStore {
+            entries: Mutex::new(Entries {
+                revoked: HashMap::new(),
+                last_prune: 0,
+            }),
+            prune_interval: 60,
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Sets how often, in seconds, expired entries are pruned. Defaults to 60.
+    pub fn with_prune_interval(mut self, prune_interval: u64) -> Self {
+        self.prune_interval = prune_interval;
+        self
+    }
+
+    /// Sets the clock entries expire by. Use the one of the
+    /// [`JwtService`](super::jwt_service::JwtService) that checks the tokens.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// The number of entries currently stored, expired or not.
+    pub fn len(&self) -> usize {
+        self.lock().revoked.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Entries> {
+        self.entries
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Default for InMemoryRevocationStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl RevocationStore for InMemoryRevocationStore {
+    async fn revoke(&self, jti: &str, expires_at: u64) -> Result<(), AuthError> {
+        let now = self.clock.now();
+        let mut entries = self.lock();
+
+        if now.saturating_sub(entries.last_prune) >= self.prune_interval {
+            entries.revoked.retain(|_, expires_at| *expires_at >= now);
+            entries.last_prune = now;
+        }
+
+        let entry = entries.revoked.entry(jti.to_string()).or_default();
+        *entry = (*entry).max(expires_at);
+
+        Ok(())
+    }
+
+    async fn is_revoked(&self, jti: &str) -> Result<bool, AuthError> {
+        let now = self.clock.now();
+
+        Ok(self
+            .lock()
+            .revoked
+            .get(jti)
+            .is_some_and(|expires_at| *expires_at >= now))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::service::clock::MockClock;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_revoke() {
+        let store = InMemoryRevocationStore::new();
+
+        store.revoke("a", u64::MAX).await.unwrap();
+
+        assert!(store.is_revoked("a").await.unwrap());
+        assert!(!store.is_revoked("b").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_expired_entries_are_pruned() {
+        let clock = Arc::new(MockClock::new(1_000));
+        let store = InMemoryRevocationStore::new()
+            .with_clock(clock.clone())
+            .with_prune_interval(60);
+
+        store.revoke("short", 1_010).await.unwrap();
+        store.revoke("long", 5_000).await.unwrap();
+
+        clock.advance(Duration::from_secs(30));
+        assert!(!store.is_revoked("short").await.unwrap());
+
+        // Too soon for another prune.
+        store.revoke("other", 5_000).await.unwrap();
+        assert_eq!(store.len(), 3);
+
+        clock.advance(Duration::from_secs(30));
+        store.revoke("another", 5_000).await.unwrap();
+        assert_eq!(store.len(), 3);
+        assert!(store.is_revoked("long").await.unwrap());
+    }
+}
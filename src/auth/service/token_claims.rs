@@ -0,0 +1,349 @@
+use crate::auth::error::AuthError;
+use crate::auth::service::clock::{Clock, SystemClock};
+#[cfg(any(feature = "jwt", feature = "paseto"))]
+use crate::auth::service::jwt_validation_config::JwtValidationConfig;
+use crate::auth::service::jwt_validation_config::RegisteredClaim;
+#[cfg(any(feature = "jwt", feature = "paseto"))]
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+#[cfg(any(feature = "jwt", feature = "paseto"))]
+use base64::Engine;
+#[cfg(any(feature = "jwt", feature = "paseto"))]
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+#[cfg(any(feature = "jwt", feature = "paseto"))]
+use serde_json::Value;
+
+/// Where the payload of a [`JwtDataContainer`] goes in the JWT claims.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ClaimsLayout {
+    /// Under a `data` claim, next to the registered claims. Only readable by services
+    /// that know the convention.
+    #[default]
+    Nested,
+    /// Merged into the top-level claims, as issued by Keycloak, Auth0 and most other
+    /// providers. The payload must serialize to a JSON object, and is deserialized
+    /// from all claims, registered ones included, so it must not deny unknown fields.
+    /// Registered claims win over payload fields of the same name.
+    Flat,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(bound(deserialize = "T: DeserializeOwned", serialize = "T: Serialize"))] // Add serde bounds
+pub struct JwtDataContainer<T>
+where
+    T: Serialize + DeserializeOwned,
+{
+    pub data: T,
+    exp: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    iss: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sub: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty", with = "audience")]
+    aud: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    iat: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    nbf: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    jti: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    act: Option<Actor>,
+}
+
+/// The `act` claim (RFC 8693 section 4.1): the party acting on behalf of the subject.
+///
+/// When a token obtained by delegation is delegated again, the previous actor is
+/// nested under the new one, most recent first.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Actor {
+    pub sub: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub act: Option<Box<Actor>>,
+}
+
+impl Actor {
+    pub fn new(subject: &str) -> Actor {
+        Actor {
+            sub: subject.to_string(),
+            act: None,
+        }
+    }
+
+    /// Records that this actor acts on behalf of `previous`.
+    pub fn with_previous(mut self, previous: Option<Actor>) -> Self {
+        self.act = previous.map(Box::new);
+        self
+    }
+}
+
+impl<T> JwtDataContainer<T>
+where
+    T: Serialize + DeserializeOwned,
+{
+    /// Replaces the payload, keeping the registered claims.
+    pub(crate) fn try_map<U>(
+        self,
+        f: impl FnOnce(T) -> Result<U, AuthError>,
+    ) -> Result<JwtDataContainer<U>, AuthError>
+    where
+        U: Serialize + DeserializeOwned,
+    {
+        Ok(JwtDataContainer {
+            data: f(self.data)?,
+            exp: self.exp,
+            iss: self.iss,
+            sub: self.sub,
+            aud: self.aud,
+            iat: self.iat,
+            nbf: self.nbf,
+            jti: self.jti,
+            act: self.act,
+        })
+    }
+
+    /// Sets `iat` and `jti` when the caller didn't, as done for every issued token.
+    #[cfg(any(feature = "jwt", feature = "paseto"))]
+    pub(crate) fn stamp(&mut self, now: u64) {
+        self.iat.get_or_insert(now);
+        self.jti.get_or_insert_with(generate_jwt_id);
+    }
+
+    /// A token payload expiring at `exp`.
+    ///
+    /// `iat` is set to the signing service's clock when the token is generated.
+    pub fn new(data: T, exp: u64) -> Self {
+        JwtDataContainer {
+            data,
+            exp,
+            iss: None,
+            sub: None,
+            aud: Vec::new(),
+            iat: None,
+            nbf: None,
+            jti: None,
+            act: None,
+        }
+    }
+
+    pub fn with_issuer(mut self, issuer: &str) -> Self {
+        self.iss = Some(issuer.to_string());
+        self
+    }
+
+    pub fn with_subject(mut self, subject: &str) -> Self {
+        self.sub = Some(subject.to_string());
+        self
+    }
+
+    /// Adds a service the token is meant for.
+    pub fn with_audience(mut self, audience: &str) -> Self {
+        self.aud.push(audience.to_string());
+        self
+    }
+
+    pub fn with_not_before(mut self, not_before: u64) -> Self {
+        self.nbf = Some(not_before);
+        self
+    }
+
+    pub fn with_jwt_id(mut self, jwt_id: &str) -> Self {
+        self.jti = Some(jwt_id.to_string());
+        self
+    }
+
+    pub fn with_actor(mut self, actor: Actor) -> Self {
+        self.act = Some(actor);
+        self
+    }
+
+    /// The `exp` claim, in seconds since the epoch.
+    pub fn expires_at(&self) -> u64 {
+        self.exp
+    }
+
+    /// The `iss` claim.
+    pub fn issuer(&self) -> Option<&str> {
+        self.iss.as_deref()
+    }
+
+    /// The `sub` claim.
+    pub fn subject(&self) -> Option<&str> {
+        self.sub.as_deref()
+    }
+
+    /// The `aud` claim, empty when the token has none.
+    pub fn audience(&self) -> &[String] {
+        &self.aud
+    }
+
+    /// The `iat` claim, in seconds since the epoch.
+    pub fn issued_at(&self) -> Option<u64> {
+        self.iat
+    }
+
+    /// The `nbf` claim, in seconds since the epoch.
+    pub fn not_before(&self) -> Option<u64> {
+        self.nbf
+    }
+
+    /// The `jti` claim.
+    pub fn jwt_id(&self) -> Option<&str> {
+        self.jti.as_deref()
+    }
+
+    /// The `act` claim, set on tokens obtained by token exchange.
+    pub fn actor(&self) -> Option<&Actor> {
+        self.act.as_ref()
+    }
+
+    /// Returns `true` if the token carries the claim.
+    pub fn has_claim(&self, claim: RegisteredClaim) -> bool {
+        match claim {
+            RegisteredClaim::Iss => self.iss.is_some(),
+            RegisteredClaim::Sub => self.sub.is_some(),
+            RegisteredClaim::Aud => !self.aud.is_empty(),
+            RegisteredClaim::Iat => self.iat.is_some(),
+            RegisteredClaim::Nbf => self.nbf.is_some(),
+            RegisteredClaim::Jti => self.jti.is_some(),
+        }
+    }
+}
+
+/// `aud` is either a single string or an array of them.
+pub(crate) mod audience {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Audience {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    pub fn serialize<S: Serializer>(audience: &[String], serializer: S) -> Result<S::Ok, S::Error> {
+        match audience {
+            [one] => serializer.serialize_str(one),
+            many => serializer.collect_seq(many),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<String>, D::Error> {
+        Ok(match Audience::deserialize(deserializer)? {
+            Audience::One(one) => vec![one],
+            Audience::Many(many) => many,
+        })
+    }
+}
+
+/// A random `jti`, 128 bits encoded as base64url.
+#[cfg(any(feature = "jwt", feature = "paseto"))]
+fn generate_jwt_id() -> String {
+    let mut bytes = [0u8; 16];
+    OsRng.fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// Checks the registered claims of a verified token against `config`, whatever its
+/// format.
+///
+/// `exp` and `nbf` are checked here, against the caller's clock.
+#[cfg(any(feature = "jwt", feature = "paseto"))]
+pub(crate) fn check_claims<T>(
+    claims: &JwtDataContainer<T>,
+    config: &JwtValidationConfig,
+    now: u64,
+) -> Result<(), AuthError>
+where
+    T: Serialize + DeserializeOwned,
+{
+    if let Some(issuer) = &config.issuer
+        && claims.iss.as_ref() != Some(issuer)
+    {
+        return Err(AuthError::InvalidToken);
+    }
+    if !config.audience.is_empty()
+        && !claims
+            .aud
+            .iter()
+            .any(|audience| config.audience.contains(audience))
+    {
+        return Err(AuthError::InvalidToken);
+    }
+
+    if claims.exp.saturating_add(config.leeway) < now {
+        return Err(AuthError::TokenExpired);
+    }
+    if claims
+        .nbf
+        .is_some_and(|nbf| nbf > now.saturating_add(config.leeway))
+    {
+        return Err(AuthError::TokenNotValid);
+    }
+
+    if !config
+        .required_claims
+        .iter()
+        .all(|claim| claims.has_claim(*claim))
+    {
+        return Err(AuthError::InvalidToken);
+    }
+
+    Ok(())
+}
+
+/// Turns a container into the JWT claims for `layout`.
+#[cfg(any(feature = "jwt", feature = "paseto"))]
+pub(crate) fn layout_claims<T>(
+    data: &JwtDataContainer<T>,
+    layout: ClaimsLayout,
+) -> Result<Value, AuthError>
+where
+    T: Serialize + DeserializeOwned,
+{
+    let mut claims = serde_json::to_value(data).map_err(|_| AuthError::InternalError)?;
+
+    if layout == ClaimsLayout::Flat {
+        let Value::Object(claims) = &mut claims else {
+            return Err(AuthError::InternalError);
+        };
+        let Some(Value::Object(payload)) = claims.remove("data") else {
+            return Err(AuthError::InternalError);
+        };
+
+        for (name, value) in payload {
+            claims.entry(name).or_insert(value);
+        }
+    }
+
+    Ok(claims)
+}
+
+/// Reads a container back from the JWT claims for `layout`.
+#[cfg(any(feature = "jwt", feature = "paseto"))]
+pub(crate) fn unlayout_claims<T>(
+    mut claims: Value,
+    layout: ClaimsLayout,
+) -> Result<JwtDataContainer<T>, AuthError>
+where
+    T: Serialize + DeserializeOwned,
+{
+    if layout == ClaimsLayout::Flat {
+        let payload = claims.clone();
+        let Value::Object(claims) = &mut claims else {
+            return Err(AuthError::InvalidToken);
+        };
+        claims.insert("data".to_string(), payload);
+    }
+
+    serde_json::from_value(claims).map_err(|_| AuthError::InvalidToken)
+}
+
+/// The current time of the [`SystemClock`].
+pub fn get_current_time() -> u64 {
+    SystemClock.now()
+}
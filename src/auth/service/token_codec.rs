@@ -0,0 +1,119 @@
+use crate::auth::error::AuthError;
+#[cfg(feature = "jwt")]
+use crate::auth::service::jwt_service::JwtService;
+use crate::auth::service::token_claims::JwtDataContainer;
+use actix_web::{HttpRequest, web};
+use async_trait::async_trait;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::sync::Arc;
+
+/// A token format: how claims are turned into a token and read back.
+///
+/// [`JwtService`], behind the default `jwt` feature, is the JWT implementation and
+/// [`PasetoService`](super::paseto_service::PasetoService), behind the `paseto` feature,
+/// the PASETO one. Code that issues or checks tokens through an
+/// `Arc<dyn TokenCodec>` works with either, so the format is a configuration choice.
+///
+/// The methods work on JSON payloads to keep the trait object safe. The typed
+/// `generate_token` and `verify_token` helpers are implemented on `dyn TokenCodec`.
+///
+/// To use a codec with the [`Authenticated`](crate::auth::extractors::authenticated::Authenticated)
+/// extractor and the other request helpers, register it as `web::Data<dyn TokenCodec>`.
+/// With the `jwt` feature, a `web::Data<JwtService>` still works when no codec is
+/// registered.
+///
+/// # Example
+/// ```
+/// # #[cfg(feature = "jwt")]
+/// # fn main() {
+/// use jsonwebtoken::Algorithm;
+/// use lunna_actix_utils::auth::service::jwt_key::JwtKey;
+/// use lunna_actix_utils::auth::service::jwt_service::{JwtService, get_current_time};
+/// use lunna_actix_utils::auth::service::token_codec::TokenCodec;
+/// use std::sync::Arc;
+///
+/// let codec: Arc<dyn TokenCodec> = Arc::new(JwtService::with_key(
+///     JwtKey::hmac(Algorithm::HS256, b"internal tools secret").unwrap(),
+/// ));
+///
+/// let token = codec.generate_token("lunna".to_string(), get_current_time() + 60).unwrap();
+/// assert_eq!(codec.verify_token::<String>(&token).unwrap().data, "lunna");
+/// # }
+/// # #[cfg(not(feature = "jwt"))]
+/// # fn main() {}
+/// ```
+#[async_trait]
+pub trait TokenCodec: Send + Sync {
+    /// Issues a token. `iat` and `jti` are set when the container has none.
+    fn encode(&self, claims: JwtDataContainer<Value>) -> Result<String, AuthError>;
+
+    /// Checks a token and its registered claims.
+    ///
+    /// Fails with [`AuthError::RevocationCheckRequired`] if the codec has a revocation
+    /// store, which only [`TokenCodec::decode_async`] can consult.
+    fn decode(&self, token: &str) -> Result<JwtDataContainer<Value>, AuthError>;
+
+    /// Checks a token like [`TokenCodec::decode`], then rejects it with
+    /// [`AuthError::TokenRevoked`] if it was revoked.
+    async fn decode_async(&self, token: &str) -> Result<JwtDataContainer<Value>, AuthError>;
+
+    /// Revokes a token until it expires.
+    async fn revoke(&self, token: &str) -> Result<(), AuthError>;
+}
+
+impl dyn TokenCodec {
+    pub fn generate_token<T>(&self, data: T, until: u64) -> Result<String, AuthError>
+    where
+        T: Serialize + DeserializeOwned,
+    {
+        self.generate_token_with_data_container(JwtDataContainer::new(data, until))
+    }
+
+    pub fn generate_token_with_data_container<T>(
+        &self,
+        data: JwtDataContainer<T>,
+    ) -> Result<String, AuthError>
+    where
+        T: Serialize + DeserializeOwned,
+    {
+        self.encode(
+            data.try_map(|data| serde_json::to_value(data).map_err(|_| AuthError::InternalError))?,
+        )
+    }
+
+    pub fn verify_token<T>(&self, token: &str) -> Result<JwtDataContainer<T>, AuthError>
+    where
+        T: Serialize + DeserializeOwned + Send + Sync,
+    {
+        self.decode(token)?.try_map(from_value)
+    }
+
+    pub async fn verify_token_async<T>(&self, token: &str) -> Result<JwtDataContainer<T>, AuthError>
+    where
+        T: Serialize + DeserializeOwned + Send + Sync,
+    {
+        self.decode_async(token).await?.try_map(from_value)
+    }
+}
+
+/// Reads a JSON payload back as `T`, rejecting the token if it doesn't fit.
+pub(crate) fn from_value<T: DeserializeOwned>(data: Value) -> Result<T, AuthError> {
+    serde_json::from_value(data).map_err(|_| AuthError::InvalidToken)
+}
+
+/// The codec registered in the app: a `web::Data<dyn TokenCodec>`, or else, with the
+/// `jwt` feature, a `web::Data<JwtService>`.
+pub(crate) fn app_token_codec(req: &HttpRequest) -> Option<Arc<dyn TokenCodec>> {
+    if let Some(codec) = req.app_data::<web::Data<dyn TokenCodec>>() {
+        return Some(codec.clone().into_inner());
+    }
+
+    #[cfg(feature = "jwt")]
+    if let Some(jwt_service) = req.app_data::<web::Data<JwtService>>() {
+        return Some(jwt_service.clone().into_inner());
+    }
+
+    None
+}
@@ -0,0 +1,318 @@
+use crate::auth::error::AuthError;
+use crate::auth::response::token_response::TokenResponse;
+use crate::auth::service::refresh_token_service::random_token;
+use actix_web::cookie::time::Duration;
+use actix_web::cookie::{Cookie, SameSite};
+use actix_web::http::Method;
+use actix_web::{HttpRequest, web};
+use std::borrow::Cow;
+
+/// The name, path and lifetime of one of the cookies set by [`TokenCookieConfig`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CookieSettings {
+    pub name: String,
+    /// Only requests under this path get the cookie.
+    pub path: String,
+    /// Seconds the browser keeps the cookie.
+    pub max_age: u64,
+}
+
+impl CookieSettings {
+    pub fn new(name: &str, path: &str, max_age: u64) -> CookieSettings {
+        CookieSettings {
+            name: name.to_string(),
+            path: path.to_string(),
+            max_age,
+        }
+    }
+}
+
+/// How tokens are carried in cookies for browser frontends, so that scripts never get
+/// to read them.
+///
+/// Both tokens are sent in `HttpOnly` cookies. Since the browser attaches them to
+/// every request, including forged cross-site ones, state-changing requests also have
+/// to pass a double-submit CSRF check: a random token is set in a cookie scripts *can*
+/// read, and the frontend echoes it in the CSRF header. A cross-site page can neither
+/// read the cookie nor set the header.
+///
+/// Register it as `web::Data<TokenCookieConfig>` to be picked up by
+/// [`TokenCookieResponse`](crate::auth::response::token_cookie_response::TokenCookieResponse)
+/// and [`CookieAuthenticated`](crate::auth::extractors::cookie_authenticated::CookieAuthenticated).
+/// The default is used otherwise.
+///
+/// Keep the short token cookie as long-lived as the short token itself. Restricting the
+/// long token path to the renew endpoint keeps it out of every other request.
+///
+/// # Example
+/// ```
+/// use actix_web::cookie::SameSite;
+/// use lunna_actix_utils::auth::service::token_cookie_config::{
+///     CookieSettings, TokenCookieConfig,
+/// };
+///
+/// let config = TokenCookieConfig::default()
+///     .with_short_token(CookieSettings::new("session", "/", 5 * 60))
+///     .with_long_token(CookieSettings::new("refresh", "/auth", 7 * 24 * 60 * 60))
+///     .with_same_site(SameSite::Lax);
+///
+/// assert_eq!(config.csrf_header, "X-CSRF-Token");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenCookieConfig {
+    /// Defaults to `short_token` on `/`, for 15 minutes.
+    pub short_token: CookieSettings,
+    /// Defaults to `long_token` on `/`, for 30 days.
+    pub long_token: CookieSettings,
+    /// The cookie holding the CSRF token. Defaults to `csrf_token` on `/`, as long-lived
+    /// as the long token.
+    pub csrf_token: CookieSettings,
+    /// The header the frontend echoes the CSRF token in. Defaults to `X-CSRF-Token`.
+    pub csrf_header: String,
+    /// The `Domain` of every cookie. Unset by default, which limits them to the host
+    /// that set them.
+    pub domain: Option<String>,
+    /// Whether the cookies are only sent over HTTPS. Defaults to `true`.
+    pub secure: bool,
+    /// Defaults to `SameSite=Strict`.
+    pub same_site: SameSite,
+}
+
+impl Default for TokenCookieConfig {
+    fn default() -> Self {
+        TokenCookieConfig {
+            short_token: CookieSettings::new("short_token", "/", 15 * 60),
+            long_token: CookieSettings::new("long_token", "/", 30 * 24 * 60 * 60),
+            csrf_token: CookieSettings::new("csrf_token", "/", 30 * 24 * 60 * 60),
+            csrf_header: "X-CSRF-Token".to_string(),
+            domain: None,
+            secure: true,
+            same_site: SameSite::Strict,
+        }
+    }
+}
+
+impl TokenCookieConfig {
+    pub fn with_short_token(mut self, short_token: CookieSettings) -> Self {
+        self.short_token = short_token;
+        self
+    }
+
+    pub fn with_long_token(mut self, long_token: CookieSettings) -> Self {
+        self.long_token = long_token;
+        self
+    }
+
+    pub fn with_csrf_token(mut self, csrf_token: CookieSettings) -> Self {
+        self.csrf_token = csrf_token;
+        self
+    }
+
+    pub fn with_csrf_header(mut self, csrf_header: &str) -> Self {
+        self.csrf_header = csrf_header.to_string();
+        self
+    }
+
+    pub fn with_domain(mut self, domain: &str) -> Self {
+        self.domain = Some(domain.to_string());
+        self
+    }
+
+    /// Only turn this off for local development over plain HTTP.
+    pub fn with_secure(mut self, secure: bool) -> Self {
+        self.secure = secure;
+        self
+    }
+
+    pub fn with_same_site(mut self, same_site: SameSite) -> Self {
+        self.same_site = same_site;
+        self
+    }
+
+    /// The cookies carrying `tokens` on login or registration, along with a fresh CSRF
+    /// token.
+    pub fn cookies(&self, tokens: &TokenResponse) -> Vec<Cookie<'static>> {
+        let mut cookies = self.renew_cookies(tokens);
+        cookies.push(self.cookie(&self.csrf_token, random_token(), false));
+        cookies
+    }
+
+    /// The cookies carrying `tokens` on renew.
+    ///
+    /// The CSRF cookie is left alone, so requests in flight during the renew keep a
+    /// valid CSRF token. The long token is only replaced when `tokens` has one, that is
+    /// when it was rotated.
+    pub fn renew_cookies(&self, tokens: &TokenResponse) -> Vec<Cookie<'static>> {
+        let mut cookies = vec![self.cookie(&self.short_token, tokens.short_token.clone(), true)];
+
+        if let Some(long_token) = &tokens.long_token {
+            cookies.push(self.cookie(&self.long_token, long_token.clone(), true));
+        }
+
+        cookies
+    }
+
+    /// Cookies that make the browser drop every cookie set by [`Self::cookies`], for
+    /// logout.
+    pub fn removal_cookies(&self) -> Vec<Cookie<'static>> {
+        [&self.short_token, &self.long_token, &self.csrf_token]
+            .into_iter()
+            .map(|settings| {
+                let mut cookie = self.cookie(settings, String::new(), true);
+                cookie.make_removal();
+                cookie
+            })
+            .collect()
+    }
+
+    /// The `web::Data<TokenCookieConfig>` of the app, or the default.
+    pub(crate) fn of(req: &HttpRequest) -> Cow<'_, TokenCookieConfig> {
+        match req.app_data::<web::Data<TokenCookieConfig>>() {
+            Some(config) => Cow::Borrowed(config.get_ref()),
+            None => Cow::Owned(TokenCookieConfig::default()),
+        }
+    }
+
+    /// Reads a token cookie back from a request.
+    pub(crate) fn token(&self, req: &HttpRequest, settings: &CookieSettings) -> Option<String> {
+        req.cookie(&settings.name)
+            .map(|cookie| cookie.value().to_string())
+            .filter(|token| !token.is_empty())
+    }
+
+    /// The double-submit check: on state-changing methods, the CSRF header must match
+    /// the CSRF cookie.
+    pub(crate) fn verify_csrf(&self, req: &HttpRequest) -> Result<(), AuthError> {
+        if is_safe(req.method()) {
+            return Ok(());
+        }
+
+        let cookie = self
+            .token(req, &self.csrf_token)
+            .ok_or(AuthError::InvalidCsrfToken)?;
+        let header = req
+            .headers()
+            .get(&self.csrf_header)
+            .and_then(|header| header.to_str().ok())
+            .ok_or(AuthError::InvalidCsrfToken)?;
+
+        if !constant_time_eq(cookie.as_bytes(), header.as_bytes()) {
+            return Err(AuthError::InvalidCsrfToken);
+        }
+
+        Ok(())
+    }
+
+    fn cookie(&self, settings: &CookieSettings, value: String, http_only: bool) -> Cookie<'static> {
+        let mut cookie = Cookie::build(settings.name.clone(), value)
+            .path(settings.path.clone())
+            .max_age(Duration::seconds(
+                i64::try_from(settings.max_age).unwrap_or(i64::MAX),
+            ))
+            .http_only(http_only)
+            .secure(self.secure)
+            .same_site(self.same_site)
+            .finish();
+
+        if let Some(domain) = &self.domain {
+            cookie.set_domain(domain.clone());
+        }
+
+        cookie
+    }
+}
+
+/// Methods that must not change state (RFC 9110), so they skip the CSRF check.
+fn is_safe(method: &Method) -> bool {
+    matches!(
+        *method,
+        Method::GET | Method::HEAD | Method::OPTIONS | Method::TRACE
+    )
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+
+    fn tokens(long_token: Option<&str>) -> TokenResponse {
+        TokenResponse {
+            long_token: long_token.map(str::to_string),
+            short_token: "short".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_cookies() {
+        let config = TokenCookieConfig::default()
+            .with_long_token(CookieSettings::new("refresh", "/auth", 60))
+            .with_domain("lunna.dev");
+
+        let cookies = config.cookies(&tokens(Some("long")));
+        assert_eq!(cookies.len(), 3);
+
+        let long_token = cookies.iter().find(|c| c.name() == "refresh").unwrap();
+        assert_eq!(long_token.value(), "long");
+        assert_eq!(long_token.path(), Some("/auth"));
+        assert_eq!(long_token.max_age(), Some(Duration::seconds(60)));
+        assert_eq!(long_token.domain(), Some("lunna.dev"));
+        assert_eq!(long_token.http_only(), Some(true));
+        assert_eq!(long_token.secure(), Some(true));
+        assert_eq!(long_token.same_site(), Some(SameSite::Strict));
+
+        let csrf_token = cookies.iter().find(|c| c.name() == "csrf_token").unwrap();
+        assert_eq!(csrf_token.http_only(), Some(false));
+        assert!(!csrf_token.value().is_empty());
+
+        let renewed = config.renew_cookies(&tokens(None));
+        assert_eq!(renewed.len(), 1);
+        assert_eq!(renewed[0].name(), "short_token");
+
+        for cookie in config.removal_cookies() {
+            assert_eq!(cookie.value(), "");
+            assert_eq!(cookie.max_age(), Some(Duration::ZERO));
+        }
+    }
+
+    #[test]
+    fn test_verify_csrf() {
+        let config = TokenCookieConfig::default();
+        let request = |method: Method, cookie: Option<&str>, header: Option<&str>| {
+            let mut request = TestRequest::default().method(method);
+            if let Some(cookie) = cookie {
+                request = request.cookie(Cookie::new("csrf_token", cookie.to_string()));
+            }
+            if let Some(header) = header {
+                request = request.insert_header(("X-CSRF-Token", header));
+            }
+            request.to_http_request()
+        };
+
+        assert!(
+            config
+                .verify_csrf(&request(Method::GET, None, None))
+                .is_ok()
+        );
+        assert!(
+            config
+                .verify_csrf(&request(Method::POST, Some("abc"), Some("abc")))
+                .is_ok()
+        );
+
+        for (cookie, header) in [
+            (None, None),
+            (Some("abc"), None),
+            (None, Some("abc")),
+            (Some("abc"), Some("abd")),
+        ] {
+            assert!(matches!(
+                config.verify_csrf(&request(Method::POST, cookie, header)),
+                Err(AuthError::InvalidCsrfToken)
+            ));
+        }
+    }
+}
@@ -0,0 +1,456 @@
+use crate::auth::error::AuthError;
+use crate::auth::service::clock::{Clock, SystemClock};
+use crate::auth::service::token_claims::{Actor, JwtDataContainer};
+use crate::auth::service::token_codec::TokenCodec;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+/// A token minted by [`TokenExchangeService::exchange`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExchangedToken {
+    pub token: String,
+    /// Seconds until the token expires.
+    pub expires_in: u64,
+    /// The scopes of the token, separated by spaces, if the subject token had any.
+    pub scope: Option<String>,
+}
+
+/// Trades a verified subject token for a narrower one (RFC 8693), so that a service
+/// holding a user's token can call another service without forwarding it.
+///
+/// The new token keeps the `iss`, `sub` and payload of the subject token, and gets:
+/// - a single `aud`, which must be allowed for the calling client with
+///   [`TokenExchangeService::allow_audience`];
+/// - the requested scopes, which must all be in the subject token;
+/// - an `exp` no later than [`TokenExchangeService::with_lifetime`] from now, nor than
+///   the subject token's;
+/// - an `act` claim naming the calling client, nesting any previous actor.
+///
+/// The subject token must have been issued to the calling client: `client_id` must be
+/// in its `aud`. Tokens without an `aud`, or meant for other services, including the
+/// output of a previous exchange, can't be exchanged by a client that got hold of them.
+///
+/// # Security
+///
+/// Exchanged tokens are signed by the same codec as the subject tokens, so **every
+/// service verifying tokens must set its own audience** with
+/// [`JwtValidationConfig::with_audience`](super::jwt_validation_config::JwtValidationConfig::with_audience).
+/// Otherwise a token exchanged for `billing` is also accepted as a full session token
+/// by every other service, the one issuing the subject tokens included.
+///
+/// Scopes are read from a `scope` string or a `scopes` array in the token data, and
+/// written back in the same shape.
+///
+/// # Example
+/// ```
+/// # #[cfg(feature = "jwt")]
+/// # #[tokio::main]
+/// # async fn main() {
+/// use jsonwebtoken::Algorithm;
+/// use lunna_actix_utils::auth::service::jwt_key::JwtKey;
+/// use lunna_actix_utils::auth::service::jwt_service::{JwtService, get_current_time};
+/// use lunna_actix_utils::auth::service::token_claims::JwtDataContainer;
+/// use lunna_actix_utils::auth::service::token_codec::TokenCodec;
+/// use lunna_actix_utils::auth::service::token_exchange_service::TokenExchangeService;
+/// use serde_json::{Value, json};
+///
+/// let jwt_service =
+///     JwtService::with_key(JwtKey::hmac(Algorithm::HS256, b"internal tools secret").unwrap());
+/// let exchange = TokenExchangeService::new().allow_audience("edge", "billing");
+///
+/// // The user logged in through `edge`.
+/// let user_token = jwt_service
+///     .generate_token_with_data_container(
+///         JwtDataContainer::new(json!({"scope": "read write"}), get_current_time() + 900)
+///             .with_audience("edge"),
+///     )
+///     .unwrap();
+/// let exchanged = exchange
+///     .exchange(&jwt_service, &user_token, "edge", "billing", Some("read"))
+///     .await
+///     .unwrap();
+///
+/// let claims = jwt_service.verify_token::<Value>(&exchanged.token).unwrap();
+/// assert_eq!(claims.audience(), ["billing"]);
+/// assert_eq!(claims.actor().unwrap().sub, "edge");
+/// assert_eq!(claims.data["scope"], "read");
+/// # }
+/// # #[cfg(not(feature = "jwt"))]
+/// # fn main() {}
+/// ```
+pub struct TokenExchangeService {
+    lifetime: u64,
+    audiences: HashMap<String, HashSet<String>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl TokenExchangeService {
+    /// A service allowing no audience, minting tokens valid for at most 5 minutes.
+    pub fn new() -> TokenExchangeService {
+        TokenExchangeService {
+            lifetime: 300,
+            audiences: HashMap::new(),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Sets the longest lifetime, in seconds, of the minted tokens.
+    pub fn with_lifetime(mut self, lifetime: u64) -> Self {
+        self.lifetime = lifetime;
+        self
+    }
+
+    /// Lets `client_id` get tokens for `audience`.
+    pub fn allow_audience(mut self, client_id: &str, audience: &str) -> Self {
+        self.audiences
+            .entry(client_id.to_string())
+            .or_default()
+            .insert(audience.to_string());
+        self
+    }
+
+    /// Sets the clock the lifetime is counted from. Use the one of the codec.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Verifies `subject_token` with `codec`, revocation included, and mints the
+    /// narrower token for `audience` on behalf of the authenticated `client_id`.
+    ///
+    /// `scope` is a space separated list. When it is `None` the scopes of the subject
+    /// token are kept.
+    pub async fn exchange(
+        &self,
+        codec: &dyn TokenCodec,
+        subject_token: &str,
+        client_id: &str,
+        audience: &str,
+        scope: Option<&str>,
+    ) -> Result<ExchangedToken, AuthError> {
+        let allowed = self
+            .audiences
+            .get(client_id)
+            .is_some_and(|audiences| audiences.contains(audience));
+        if !allowed {
+            return Err(AuthError::InvalidTarget);
+        }
+
+        let subject = codec
+            .decode_async(subject_token)
+            .await
+            .map_err(|err| match err {
+                AuthError::InvalidToken
+                | AuthError::TokenExpired
+                | AuthError::TokenNotFound
+                | AuthError::TokenNotValid
+                | AuthError::TokenRevoked
+                | AuthError::TokenReused => AuthError::InvalidGrant,
+                err => err,
+            })?;
+
+        if !subject.audience().iter().any(|aud| aud == client_id) {
+            return Err(AuthError::InvalidGrant);
+        }
+
+        let mut data = subject.data.clone();
+        let scope = restrict_scope(&mut data, scope)?;
+
+        let now = self.clock.now();
+        let exp = subject.expires_at().min(now + self.lifetime);
+        let mut exchanged = JwtDataContainer::new(data, exp)
+            .with_audience(audience)
+            .with_actor(Actor::new(client_id).with_previous(subject.actor().cloned()));
+        if let Some(issuer) = subject.issuer() {
+            exchanged = exchanged.with_issuer(issuer);
+        }
+        if let Some(sub) = subject.subject() {
+            exchanged = exchanged.with_subject(sub);
+        }
+
+        Ok(ExchangedToken {
+            token: codec.encode(exchanged)?,
+            expires_in: exp.saturating_sub(now),
+            scope,
+        })
+    }
+}
+
+impl Default for TokenExchangeService {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Narrows the scopes in the token data to `requested`, returning the scopes of the
+/// new token.
+fn restrict_scope(data: &mut Value, requested: Option<&str>) -> Result<Option<String>, AuthError> {
+    let granted: Option<Vec<String>> = match data.get("scope") {
+        Some(Value::String(scope)) => Some(scope.split_whitespace().map(str::to_string).collect()),
+        _ => data.get("scopes").and_then(Value::as_array).map(|scopes| {
+            scopes
+                .iter()
+                .filter_map(Value::as_str)
+                .map(str::to_string)
+                .collect()
+        }),
+    };
+
+    let requested = requested.filter(|requested| !requested.trim().is_empty());
+    let Some(requested) = requested else {
+        return Ok(granted.map(|granted| granted.join(" ")));
+    };
+
+    let granted = granted.unwrap_or_default();
+    let requested: Vec<&str> = requested.split_whitespace().collect();
+    if !requested
+        .iter()
+        .all(|scope| granted.iter().any(|granted| granted == scope))
+    {
+        return Err(AuthError::InvalidScope);
+    }
+
+    if data.get("scope").is_some_and(Value::is_string) {
+        data["scope"] = Value::String(requested.join(" "));
+    } else {
+        data["scopes"] = Value::from(requested.clone());
+    }
+
+    Ok(Some(requested.join(" ")))
+}
+
+#[cfg(all(test, feature = "jwt"))]
+mod tests {
+    use super::*;
+    use crate::auth::service::clock::MockClock;
+    use crate::auth::service::jwt_key::JwtKey;
+    use crate::auth::service::jwt_service::JwtService;
+    use crate::auth::service::jwt_validation_config::JwtValidationConfig;
+    use crate::auth::service::revocation_store::InMemoryRevocationStore;
+    use crate::auth::test_keys::HMAC_TEST_SECRET;
+    use jsonwebtoken::Algorithm;
+    use serde_json::json;
+
+    const NOW: u64 = 1_700_000_000;
+
+    fn setup() -> (JwtService, TokenExchangeService) {
+        let clock = Arc::new(MockClock::new(NOW));
+        let jwt_service =
+            JwtService::with_key(JwtKey::hmac(Algorithm::HS256, HMAC_TEST_SECRET).unwrap())
+                .with_revocation_store(Arc::new(
+                    InMemoryRevocationStore::new().with_clock(clock.clone()),
+                ))
+                .with_clock(clock.clone());
+        let exchange = TokenExchangeService::new()
+            .with_clock(clock)
+            .allow_audience("edge", "billing");
+
+        (jwt_service, exchange)
+    }
+
+    fn user_token(jwt_service: &JwtService, data: Value) -> String {
+        jwt_service
+            .generate_token_with_data_container(
+                JwtDataContainer::new(data, NOW + 900)
+                    .with_issuer("https://auth.lunna.dev")
+                    .with_subject("42")
+                    .with_audience("edge"),
+            )
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_exchange_mints_a_narrower_token() {
+        let (jwt_service, exchange) = setup();
+        let token = user_token(&jwt_service, json!({"scopes": ["read", "write"]}));
+
+        let exchanged = exchange
+            .exchange(&jwt_service, &token, "edge", "billing", Some("read"))
+            .await
+            .unwrap();
+
+        assert_eq!(exchanged.expires_in, 300);
+        assert_eq!(exchanged.scope.as_deref(), Some("read"));
+
+        let claims = jwt_service
+            .verify_token_async::<Value>(&exchanged.token)
+            .await
+            .unwrap();
+        assert_eq!(claims.audience(), ["billing"]);
+        assert_eq!(claims.issuer(), Some("https://auth.lunna.dev"));
+        assert_eq!(claims.subject(), Some("42"));
+        assert_eq!(claims.expires_at(), NOW + 300);
+        assert_eq!(claims.actor(), Some(&Actor::new("edge")));
+        assert_eq!(claims.data, json!({"scopes": ["read"]}));
+    }
+
+    #[tokio::test]
+    async fn test_exchange_keeps_scopes_and_nests_actors() {
+        let (jwt_service, exchange) = setup();
+        let exchange = exchange.allow_audience("billing", "ledger");
+        let token = user_token(&jwt_service, json!({"scope": "read write"}));
+
+        let first = exchange
+            .exchange(&jwt_service, &token, "edge", "billing", None)
+            .await
+            .unwrap();
+        let second = exchange
+            .exchange(&jwt_service, &first.token, "billing", "ledger", None)
+            .await
+            .unwrap();
+
+        assert_eq!(second.scope.as_deref(), Some("read write"));
+
+        let claims = jwt_service
+            .verify_token_async::<Value>(&second.token)
+            .await
+            .unwrap();
+        assert_eq!(
+            claims.actor(),
+            Some(&Actor::new("billing").with_previous(Some(Actor::new("edge"))))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_exchange_never_outlives_the_subject_token() {
+        let (jwt_service, exchange) = setup();
+        let token = jwt_service
+            .generate_token_with_data_container(
+                JwtDataContainer::new(json!({}), NOW + 60).with_audience("edge"),
+            )
+            .unwrap();
+
+        let exchanged = exchange
+            .exchange(&jwt_service, &token, "edge", "billing", None)
+            .await
+            .unwrap();
+
+        assert_eq!(exchanged.expires_in, 60);
+        assert_eq!(exchanged.scope, None);
+    }
+
+    #[tokio::test]
+    async fn test_exchange_rejects_wider_scope() {
+        let (jwt_service, exchange) = setup();
+        let token = user_token(&jwt_service, json!({"scope": "read"}));
+
+        assert!(matches!(
+            exchange
+                .exchange(&jwt_service, &token, "edge", "billing", Some("read admin"))
+                .await,
+            Err(AuthError::InvalidScope)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_exchange_rejects_audience_not_allowed() {
+        let (jwt_service, exchange) = setup();
+        let token = user_token(&jwt_service, json!({}));
+
+        assert!(matches!(
+            exchange
+                .exchange(&jwt_service, &token, "edge", "ledger", None)
+                .await,
+            Err(AuthError::InvalidTarget)
+        ));
+        assert!(matches!(
+            exchange
+                .exchange(&jwt_service, &token, "billing", "billing", None)
+                .await,
+            Err(AuthError::InvalidTarget)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_exchange_rejects_token_issued_to_another_client() {
+        let (jwt_service, exchange) = setup();
+        let exchange = exchange.allow_audience("edge", "ledger");
+        let token = jwt_service
+            .generate_token_with_data_container(
+                JwtDataContainer::new(json!({}), NOW + 900)
+                    .with_subject("42")
+                    .with_audience("ledger"),
+            )
+            .unwrap();
+
+        assert!(matches!(
+            exchange
+                .exchange(&jwt_service, &token, "edge", "billing", None)
+                .await,
+            Err(AuthError::InvalidGrant)
+        ));
+
+        // Nor can the client exchange the token it just got.
+        let token = user_token(&jwt_service, json!({}));
+        let exchanged = exchange
+            .exchange(&jwt_service, &token, "edge", "ledger", None)
+            .await
+            .unwrap();
+        assert!(matches!(
+            exchange
+                .exchange(&jwt_service, &exchanged.token, "edge", "billing", None)
+                .await,
+            Err(AuthError::InvalidGrant)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_exchange_rejects_token_without_audience() {
+        let (jwt_service, exchange) = setup();
+        let token = jwt_service.generate_token(json!({}), NOW + 900).unwrap();
+
+        assert!(matches!(
+            exchange
+                .exchange(&jwt_service, &token, "edge", "billing", None)
+                .await,
+            Err(AuthError::InvalidGrant)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_exchanged_token_is_rejected_by_other_audiences() {
+        let (jwt_service, exchange) = setup();
+        let token = user_token(&jwt_service, json!({}));
+        let exchanged = exchange
+            .exchange(&jwt_service, &token, "edge", "billing", None)
+            .await
+            .unwrap();
+
+        let verifier = |audience: &str| {
+            JwtService::with_key(JwtKey::hmac(Algorithm::HS256, HMAC_TEST_SECRET).unwrap())
+                .with_clock(Arc::new(MockClock::new(NOW)))
+                .with_validation(JwtValidationConfig::default().with_audience(audience))
+        };
+
+        assert!(
+            verifier("billing")
+                .verify_token::<Value>(&exchanged.token)
+                .is_ok()
+        );
+        assert!(matches!(
+            verifier("edge").verify_token::<Value>(&exchanged.token),
+            Err(AuthError::InvalidToken)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_exchange_rejects_revoked_subject_token() {
+        let (jwt_service, exchange) = setup();
+        let token = user_token(&jwt_service, json!({}));
+        jwt_service.revoke_token(&token).await.unwrap();
+
+        assert!(matches!(
+            exchange
+                .exchange(&jwt_service, &token, "edge", "billing", None)
+                .await,
+            Err(AuthError::InvalidGrant)
+        ));
+        assert!(matches!(
+            exchange
+                .exchange(&jwt_service, "not a token", "edge", "billing", None)
+                .await,
+            Err(AuthError::InvalidGrant)
+        ));
+    }
+}
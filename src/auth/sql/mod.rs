@@ -1 +1,5 @@
-pub mod auth_service_sql;
\ No newline at end of file
+pub mod auth_service_sql;
+#[cfg(feature = "sql")]
+pub mod revocation_store_sql;
+#[cfg(feature = "sql")]
+pub mod revoked_token;
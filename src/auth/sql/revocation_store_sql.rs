@@ -0,0 +1,205 @@
+use crate::auth::error::AuthError;
+use crate::auth::service::clock::{Clock, SystemClock};
+use crate::auth::service::revocation_store::RevocationStore;
+use crate::auth::sql::revoked_token;
+use async_trait::async_trait;
+use sea_orm::sea_query::OnConflict;
+use sea_orm::{
+    ActiveValue, ColumnTrait, ConnectionTrait, DatabaseConnection, EntityTrait, QueryFilter, Schema,
+};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// A [`RevocationStore`] backed by the `revoked_tokens` table, shared by every instance
+/// of a service.
+///
+/// Expired rows are deleted whenever a token is revoked, at most once per
+/// [`SqlRevocationStore::with_prune_interval`] seconds. Revoking a token doesn't fail
+/// when deleting them does.
+///
+/// Requires the `sql` feature.
+pub struct SqlRevocationStore {
+    db: DatabaseConnection,
+    prune_interval: u64,
+    last_prune: AtomicU64,
+    clock: Arc<dyn Clock>,
+}
+
+impl SqlRevocationStore {
+    pub fn new(db: DatabaseConnection) -> SqlRevocationStore {
+        SqlRevocationStore {
+            db,
+            prune_interval: 60,
+            last_prune: AtomicU64::new(0),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Sets how often, in seconds, expired rows are deleted. Defaults to 60.
+    pub fn with_prune_interval(mut self, prune_interval: u64) -> Self {
+        self.prune_interval = prune_interval;
+        self
+    }
+
+    /// Sets the clock rows expire by.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Creates the `revoked_tokens` table if it doesn't exist yet.
+    pub async fn create_table(&self) -> Result<(), AuthError> {
+        let backend = self.db.get_database_backend();
+        let statement = Schema::new(backend)
+            .create_table_from_entity(revoked_token::Entity)
+            .if_not_exists()
+            .to_owned();
+
+        self.db
+            .execute(backend.build(&statement))
+            .await
+            .map_err(|_| AuthError::InternalError)?;
+
+        Ok(())
+    }
+
+    /// Deletes the rows of tokens that have expired.
+    pub async fn prune(&self) -> Result<(), AuthError> {
+        revoked_token::Entity::delete_many()
+            .filter(revoked_token::Column::ExpiresAt.lt(timestamp(self.clock.now())))
+            .exec(&self.db)
+            .await
+            .map_err(|_| AuthError::InternalError)?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl RevocationStore for SqlRevocationStore {
+    async fn revoke(&self, jti: &str, expires_at: u64) -> Result<(), AuthError> {
+        let now = self.clock.now();
+        let last_prune = self.last_prune.load(Ordering::Relaxed);
+
+        // Only the caller that wins the swap prunes, the others go on.
+        if now.saturating_sub(last_prune) >= self.prune_interval
+            && self
+                .last_prune
+                .compare_exchange(last_prune, now, Ordering::Relaxed, Ordering::Relaxed)
+                .is_ok()
+        {
+            // A failed prune only leaves expired rows for the next one, the token must
+            // be revoked regardless.
+            let _ = self.prune().await;
+        }
+
+        let row = revoked_token::ActiveModel {
+            jti: ActiveValue::Set(jti.to_string()),
+            expires_at: ActiveValue::Set(timestamp(expires_at)),
+        };
+
+        revoked_token::Entity::insert(row)
+            .on_conflict(
+                OnConflict::column(revoked_token::Column::Jti)
+                    .update_column(revoked_token::Column::ExpiresAt)
+                    .to_owned(),
+            )
+            .exec(&self.db)
+            .await
+            .map_err(|_| AuthError::InternalError)?;
+
+        Ok(())
+    }
+
+    async fn is_revoked(&self, jti: &str) -> Result<bool, AuthError> {
+        let row = revoked_token::Entity::find_by_id(jti)
+            .filter(revoked_token::Column::ExpiresAt.gte(timestamp(self.clock.now())))
+            .one(&self.db)
+            .await
+            .map_err(|_| AuthError::InternalError)?;
+
+        Ok(row.is_some())
+    }
+}
+
+fn timestamp(seconds: u64) -> i64 {
+    i64::try_from(seconds).unwrap_or(i64::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::service::clock::MockClock;
+    use sea_orm::{Database, DatabaseBackend, DbErr, MockDatabase, MockExecResult};
+    use std::time::Duration;
+
+    const NOW: u64 = 1_700_000_000;
+
+    async fn store() -> (SqlRevocationStore, Arc<MockClock>) {
+        let clock = Arc::new(MockClock::new(NOW));
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        let store = SqlRevocationStore::new(db).with_clock(clock.clone());
+        store.create_table().await.unwrap();
+        (store, clock)
+    }
+
+    async fn rows(store: &SqlRevocationStore) -> Vec<revoked_token::Model> {
+        revoked_token::Entity::find().all(&store.db).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_revoke() {
+        let (store, _) = store().await;
+
+        store.revoke("a", NOW + 60).await.unwrap();
+
+        assert!(store.is_revoked("a").await.unwrap());
+        assert!(!store.is_revoked("b").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_expired_rows() {
+        let (store, clock) = store().await;
+        store.revoke("a", NOW + 60).await.unwrap();
+        store.revoke("b", NOW + 600).await.unwrap();
+
+        clock.advance(Duration::from_secs(61));
+        assert!(!store.is_revoked("a").await.unwrap());
+        assert!(store.is_revoked("b").await.unwrap());
+
+        // The next revoke prunes the row of "a".
+        store.revoke("c", NOW + 600).await.unwrap();
+        let jtis: Vec<String> = rows(&store).await.into_iter().map(|row| row.jti).collect();
+        assert_eq!(jtis, ["b", "c"]);
+    }
+
+    #[tokio::test]
+    async fn test_revoking_twice_updates_the_expiry() {
+        let (store, clock) = store().await;
+
+        store.revoke("a", NOW + 10).await.unwrap();
+        store.revoke("a", NOW + 100).await.unwrap();
+
+        clock.advance(Duration::from_secs(50));
+        assert!(store.is_revoked("a").await.unwrap());
+        assert_eq!(rows(&store).await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_revoke_when_pruning_fails() {
+        let db = MockDatabase::new(DatabaseBackend::MySql)
+            .append_exec_errors([DbErr::Custom("the prune failed".to_string())])
+            .append_exec_results([MockExecResult {
+                last_insert_id: 0,
+                rows_affected: 1,
+            }])
+            .into_connection();
+        let store = SqlRevocationStore::new(db).with_clock(Arc::new(MockClock::new(NOW)));
+
+        store.revoke("a", NOW + 60).await.unwrap();
+
+        let log = store.db.into_transaction_log();
+        assert_eq!(log.len(), 2);
+        assert!(log[1].statements()[0].sql.starts_with("INSERT"));
+    }
+}
@@ -0,0 +1,17 @@
+use sea_orm::entity::prelude::*;
+
+/// A row of the `revoked_tokens` table used by
+/// [`SqlRevocationStore`](super::revocation_store_sql::SqlRevocationStore).
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
+#[sea_orm(table_name = "revoked_tokens")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub jti: String,
+    /// The `exp` of the revoked token, after which the row can be deleted.
+    pub expires_at: i64,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}